@@ -1,4 +1,37 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Rules are checked in order, the first one that applies to a stone wins.
+enum Rule {
+    /// Engrave a stone with the first number with the second number instead
+    Replace(u64, u64),
+    /// Split a stone with an even number of digits into its left and right halves
+    SplitEven,
+    /// Multiply the number on a stone
+    Multiply(u64),
+}
+
+const RULES: [Rule; 3] = [Rule::Replace(0, 1), Rule::SplitEven, Rule::Multiply(2024)];
+
+impl Rule {
+    fn apply(&self, stone: u64) -> Option<(u64, Option<u64>)> {
+        match *self {
+            Rule::Replace(from, to) => (stone == from).then_some((to, None)),
+            Rule::SplitEven => {
+                let digits = stone.checked_ilog10()? + 1;
+                if digits.is_multiple_of(2) {
+                    let cutoff = 10u64.pow(digits / 2);
+                    Some((stone / cutoff, Some(stone % cutoff)))
+                } else {
+                    None
+                }
+            }
+            Rule::Multiply(factor) => {
+                let product = stone.checked_mul(factor).expect("Stone number overflow");
+                Some((product, None))
+            }
+        }
+    }
+}
 
 fn parse(input: String) -> Vec<u64> {
     input
@@ -7,64 +40,57 @@ fn parse(input: String) -> Vec<u64> {
         .collect()
 }
 
-fn part1(input: String) {
-    let mut stones = parse(input);
-    for _ in 0..25 {
-        let mut new_stones = Vec::with_capacity(stones.len());
-        for s in &stones {
-            match s {
-                0 => new_stones.push(1),
-                n => {
-                    let digits = s.ilog10() + 1;
-                    if digits % 2 == 0 {
-                        let cutoff = 10u64.pow(digits / 2);
-                        new_stones.push(n / cutoff);
-                        new_stones.push(n % cutoff);
-                    } else {
-                        new_stones.push(n * 2024)
-                    }
-                }
-            }
+/// Since stones never interact, only the number of stones with each value matters.
+fn blink(stones: &HashMap<u64, u64>, rules: &[Rule]) -> HashMap<u64, u64> {
+    let mut next = HashMap::with_capacity(stones.len());
+    for (&stone, &count) in stones {
+        let (a, b) = rules
+            .iter()
+            .find_map(|r| r.apply(stone))
+            .unwrap_or((stone, None));
+        *next.entry(a).or_default() += count;
+        if let Some(b) = b {
+            *next.entry(b).or_default() += count;
         }
-        stones = new_stones;
     }
-    println!("{}", stones.len());
+    next
 }
 
-fn expansion(s: u64, rounds: u32, cache: &mut HashMap<(u64, u32), u64>) -> u64 {
-    // Recursion anchor
-    if rounds == 0 {
-        return 1;
+/// Counts of each distinct stone after every blink, starting with the
+/// initial arrangement, and the number of distinct values ever seen.
+fn evolve(stones: &[u64], rules: &[Rule], blinks: u32) -> (Vec<HashMap<u64, u64>>, usize) {
+    let mut counts = HashMap::new();
+    for &s in stones {
+        *counts.entry(s).or_default() += 1;
     }
-    // Calculation is already cached
-    if let Some(res) = cache.get(&(s, rounds)) {
-        return *res;
+    let mut seen: HashSet<u64> = counts.keys().copied().collect();
+    let mut history = vec![counts];
+    for _ in 0..blinks {
+        let next = blink(history.last().unwrap(), rules);
+        seen.extend(next.keys());
+        history.push(next);
     }
+    (history, seen.len())
+}
 
-    // Recurse
-    let res = match s {
-        0 => expansion(1, rounds - 1, cache),
-        n => {
-            let digits = s.ilog10() + 1;
-            if digits % 2 == 0 {
-                let cutoff = 10u64.pow(digits / 2);
-                expansion(n / cutoff, rounds - 1, cache) +
-                expansion(n % cutoff, rounds - 1, cache)
-            } else {
-                expansion(n * 2024, rounds - 1, cache)
-            }
+fn solve(input: String, blinks: u32) {
+    let (history, n_seen) = evolve(&parse(input), &RULES, blinks);
+    if util::verbose() {
+        for (i, counts) in history.iter().enumerate() {
+            println!("Blink {i}: {} distinct stones", counts.len());
         }
-    };
-    // Save in cache
-    cache.insert((s, rounds), res);
-    res
+        println!("Distinct values seen: {n_seen}");
+    }
+    let total: u64 = history.last().unwrap().values().sum();
+    println!("{total}");
+}
+
+fn part1(input: String) {
+    solve(input, 25);
 }
 
 fn part2(input: String) {
-    let stones = parse(input);
-    let mut cache = HashMap::new();
-    let sum: u64 = stones.iter().map(|s| expansion(*s, 75, &mut cache)).sum();
-    println!("{sum}");
+    solve(input, 75);
 }
 
 util::aoc_main!();
@@ -1,102 +1,143 @@
-use std::collections::{HashSet, VecDeque};
+use euclid::{default::*, vec2};
 
-use euclid::{default::*, point2, vec2};
-
-type Fences = HashSet<(Point2D<i32>, Point2D<i32>)>;
 const DIRS: [Vector2D<i32>; 4] = [vec2(0, -1), vec2(1, 0), vec2(0, 1), vec2(-1, 0)];
 
 fn parse(input: &str) -> Vec<&[u8]> {
     input.lines().map(|l| l.as_bytes()).collect()
 }
 
-fn price(field: &[&[u8]], start: (usize, usize), visited: &mut [Vec<bool>]) -> (u32, Fences) {
-    let crop = field[start.1][start.0];
-    let width = field[0].len();
-    let height = field.len();
-    let mut area_visited = vec![vec![false; width]; height];
-    let mut area = 0;
-    let mut fences: Fences = HashSet::new();
+/// Union-find over all plots, using path halving and union by size
+struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
 
-    area_visited[start.1][start.0] = true;
-    visited[start.1][start.0] = true;
-    let start = point2(start.0 as i32, start.1 as i32);
-    let bounds = Rect::new(Point2D::origin(), Size2D::new(width, height).to_i32());
-    let mut frontier = VecDeque::from([start]);
-    while let Some(p) = frontier.pop_front() {
-        area += 1;
-        for dir in DIRS {
-            let next = p + dir;
-            if bounds.contains(next) {
-                let next_u = next.to_usize();
-                if area_visited[next_u.y][next_u.x] {
-                    continue;
-                }
-                if field[next_u.y][next_u.x] == crop {
-                    visited[next_u.y][next_u.x] = true;
-                    area_visited[next_u.y][next_u.x] = true;
-                    frontier.push_back(next);
-                    continue;
-                }
-            }
-            fences.insert((p, next));
+impl DisjointSet {
+    fn new(n: usize) -> Self {
+        DisjointSet {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            (a, b) = (b, a);
         }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
     }
-    (area, fences)
 }
 
-fn part1(input: String) {
-    let field = parse(&input);
+#[derive(Clone, Copy, Default)]
+struct Region {
+    crop: u8,
+    area: u32,
+    perimeter: u32,
+    sides: u32,
+}
+
+/// Label every plot with the index of its region, in order of first appearance.
+fn label(field: &[&[u8]]) -> (Vec<Vec<usize>>, usize) {
     let width = field[0].len();
     let height = field.len();
-    let mut visited = vec![vec![false; width]; height];
-    let mut total_price = 0;
+    let mut set = DisjointSet::new(width * height);
     for y in 0..height {
         for x in 0..width {
-            if !visited[y][x] {
-                let (area, fences) = price(&field, (x, y), &mut visited);
-                total_price += area * fences.len() as u32;
+            if x + 1 < width && field[y][x] == field[y][x + 1] {
+                set.union(y * width + x, y * width + x + 1);
+            }
+            if y + 1 < height && field[y][x] == field[y + 1][x] {
+                set.union(y * width + x, (y + 1) * width + x);
             }
         }
     }
-    println!("{total_price}");
+    let mut ids = vec![usize::MAX; width * height];
+    let mut n_regions = 0;
+    let mut labels = vec![vec![0; width]; height];
+    for (y, row) in labels.iter_mut().enumerate() {
+        for (x, l) in row.iter_mut().enumerate() {
+            let root = set.find(y * width + x);
+            if ids[root] == usize::MAX {
+                ids[root] = n_regions;
+                n_regions += 1;
+            }
+            *l = ids[root];
+        }
+    }
+    (labels, n_regions)
 }
 
-fn count_perimeter(mut fences: Fences) -> u32 {
-    let list: Vec<_> = fences.iter().copied().collect();
-    let mut perimeter = 0;
-    for (v, w) in list {
-        if fences.contains(&(v, w)) {
-            perimeter += 1;
-            let dir = w - v;
-            let orth = dir.yx();
-            let mut next = v + orth;
-            while fences.remove(&(next, next + dir)) {
-                next += orth;
-            }
-            let mut next = v - orth;
-            while fences.remove(&(next, next + dir)) {
-                next -= orth;
+/// The number of sides of a region equals its number of corners. For each
+/// plot and each pair of orthogonal directions there is an outer corner if
+/// both neighbors belong to other regions, and an inner corner if both
+/// belong to the same region, but the diagonal doesn't. Holes are counted
+/// correctly, because their borders contribute corners like any other.
+fn regions(field: &[&[u8]]) -> Vec<Region> {
+    let (labels, n_regions) = label(field);
+    let width = field[0].len() as i32;
+    let height = field.len() as i32;
+    let region_at = |p: Point2D<i32>| {
+        (p.x >= 0 && p.y >= 0 && p.x < width && p.y < height)
+            .then(|| labels[p.y as usize][p.x as usize])
+    };
+    let mut regions = vec![Region::default(); n_regions];
+    for (y, row) in labels.iter().enumerate() {
+        for (x, &l) in row.iter().enumerate() {
+            let p = Point2D::new(x, y).to_i32();
+            let same = |v: Vector2D<i32>| region_at(p + v) == Some(l);
+            let region = &mut regions[l];
+            region.crop = field[y][x];
+            region.area += 1;
+            for (i, &d0) in DIRS.iter().enumerate() {
+                let d1 = DIRS[(i + 1) % 4];
+                if !same(d0) {
+                    region.perimeter += 1;
+                }
+                let outer = !same(d0) && !same(d1);
+                let inner = same(d0) && same(d1) && !same(d0 + d1);
+                if outer || inner {
+                    region.sides += 1;
+                }
             }
         }
     }
-    perimeter
+    regions
 }
 
-fn part2(input: String) {
-    let field = parse(&input);
-    let width = field[0].len();
-    let height = field.len();
-    let mut visited = vec![vec![false; width]; height];
-    let mut total_price = 0;
-    for y in 0..height {
-        for x in 0..width {
-            if !visited[y][x] {
-                let (area, fences) = price(&field, (x, y), &mut visited);
-                total_price += area * count_perimeter(fences);
-            }
+fn solve(input: &str, price: fn(&Region) -> u32) -> u32 {
+    let field = parse(input);
+    let regions = regions(&field);
+    if util::verbose() {
+        println!("crop  area  perimeter  sides");
+        for r in &regions {
+            println!(
+                "{:>4}  {:>4}  {:>9}  {:>5}",
+                r.crop as char, r.area, r.perimeter, r.sides
+            );
         }
     }
-    println!("{total_price}");
+    regions.iter().map(price).sum()
+}
+
+fn part1(input: String) {
+    println!("{}", solve(&input, |r| r.area * r.perimeter));
+}
+
+fn part2(input: String) {
+    println!("{}", solve(&input, |r| r.area * r.sides));
 }
 
 util::aoc_main!();
@@ -9,54 +9,137 @@ struct Machine {
     prize: (i64, i64),
 }
 
+/// Token cost and optional maximum number of presses of buttons A and B
+struct Pricing {
+    cost: (i64, i64),
+    limit: (Option<i64>, Option<i64>),
+}
+
+const PART1: Pricing = Pricing {
+    cost: (3, 1),
+    limit: (Some(100), Some(100)),
+};
+const PART2: Pricing = Pricing {
+    cost: (3, 1),
+    limit: (None, None),
+};
+
+fn cross(u: (i128, i128), v: (i128, i128)) -> i128 {
+    u.0 * v.1 - u.1 * v.0
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -div_floor(-a, b)
+}
+
+/// Returns `(g, x, y)` such that `a * x + b * y = g = gcd(a, b)`.
+fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a.abs(), a.signum(), 0)
+    } else {
+        let (g, x, y) = ext_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+/// Restrict the range of `t` to values where `base + t * step` lies within `lo..=hi`.
+fn restrict(
+    range: &mut (Option<i128>, Option<i128>),
+    base: i128,
+    step: i128,
+    lo: i128,
+    hi: Option<i128>,
+) {
+    if step == 0 {
+        // Doesn't depend on `t`, e.g. for a button that doesn't move the claw
+        if base < lo || hi.is_some_and(|h| base > h) {
+            *range = (Some(1), Some(0));
+        }
+        return;
+    }
+    let mut bounds = (Some(lo), hi);
+    if step < 0 {
+        bounds = (hi, Some(lo));
+    }
+    // Bound on `t` derived from `base + t * step == bound`, rounded inwards
+    if let Some(b) = bounds.0 {
+        let t = div_ceil(b - base, step);
+        range.0 = Some(range.0.map_or(t, |r| r.max(t)));
+    }
+    if let Some(b) = bounds.1 {
+        let t = div_floor(b - base, step);
+        range.1 = Some(range.1.map_or(t, |r| r.min(t)));
+    }
+}
+
 impl Machine {
-    fn tokens_100(&self) -> i64 {
-        for b in 0..=100 {
-            for a in 0..=100 {
-                let pos = (self.a.0 * a + self.b.0 * b, self.a.1 * a + self.b.1 * b);
-                if pos == self.prize {
-                    return b + 3 * a;
-                }
+    /// The minimal number of tokens needed to win the prize, if possible.
+    fn tokens(&self, pricing: &Pricing) -> Option<i64> {
+        let a = (self.a.0 as i128, self.a.1 as i128);
+        let b = (self.b.0 as i128, self.b.1 as i128);
+        let prize = (self.prize.0 as i128, self.prize.1 as i128);
+        let (ca, cb) = (pricing.cost.0 as i128, pricing.cost.1 as i128);
+        let (la, lb) = (
+            pricing.limit.0.map(i128::from),
+            pricing.limit.1.map(i128::from),
+        );
+        let within = |n: i128, limit: Option<i128>| n >= 0 && limit.is_none_or(|l| n <= l);
+
+        // Solve prize = na * a + nb * b with Cramer's rule
+        let det = cross(a, b);
+        if det != 0 {
+            let na_det = cross(prize, b);
+            let nb_det = cross(a, prize);
+            if na_det % det != 0 || nb_det % det != 0 {
+                return None;
             }
+            let (na, nb) = (na_det / det, nb_det / det);
+            return (within(na, la) && within(nb, lb)).then(|| (ca * na + cb * nb) as i64);
         }
-        0
-    }
 
-    fn tokens_inv(&self) -> i64 {
-        // If [ab] is the matrix containing our two button vectors: [ a.0 b.0 ]
-        //                                                          [ a.1 b.1 ]
-        // then prize = [ab] * x, where x holds the number of required button presses
-        // for a and b, (na, nb).
-        // By inverting [ab] we get
-        //
-        // x = [ab]⁻¹ * prize
-        let det = (self.a.0 * self.b.1) - (self.a.1 * self.b.0);
-        if det == 0 {
-            panic!("Irregular matrix");
+        // Both buttons move along the same line. Reduce everything to
+        // multiples of the smallest step `dir` on that line.
+        let Some(v) = [a, b].into_iter().find(|&v| v != (0, 0)) else {
+            return (prize == (0, 0)).then_some(0);
+        };
+        if cross(v, prize) != 0 {
+            return None;
         }
-        let det = det as f64;
-        // The matrix [ a b ] is the inverse of [ a.0 b.0 ] .
-        //            [ c d ]                   [ a.1 b.1 ]
-        let a = self.b.1 as f64 / det;
-        let b = -self.b.0 as f64 / det;
-        let c = -self.a.1 as f64 / det;
-        let d = self.a.0 as f64 / det;
-        // Multiply [ab] * prize to get the result
-        let na = self.prize.0 as f64 * a + self.prize.1 as f64 * b;
-        let nb = self.prize.0 as f64 * c + self.prize.1 as f64 * d;
-
-        // Only integer solutions are valid, verify rounded results:
-        let ina = na.round() as i64;
-        let inb = nb.round() as i64;
-        let pos = (
-            self.a.0 * ina + self.b.0 * inb,
-            self.a.1 * ina + self.b.1 * inb,
-        );
-        if pos == self.prize {
-            inb + 3 * ina
-        } else {
-            0
+        let (g, _, _) = ext_gcd(v.0, v.1);
+        let dir = (v.0 / g, v.1 / g);
+        let scalar = |u: (i128, i128)| if dir.0 != 0 { u.0 / dir.0 } else { u.1 / dir.1 };
+        let (sa, sb, sp) = (scalar(a), scalar(b), scalar(prize));
+
+        // All solutions of na * sa + nb * sb = sp are
+        //   na = na0 + t * sb / g,  nb = nb0 - t * sa / g
+        let (g, x, y) = ext_gcd(sa, sb);
+        if sp % g != 0 {
+            return None;
         }
+        let (na0, nb0) = (x * (sp / g), y * (sp / g));
+        let (step_a, step_b) = (sb / g, -sa / g);
+        let mut range = (None, None);
+        restrict(&mut range, na0, step_a, 0, la);
+        restrict(&mut range, nb0, step_b, 0, lb);
+        // Cost is linear in t, so the optimum lies at one end of the range
+        let slope = ca * step_a + cb * step_b;
+        let t = match (range, slope) {
+            ((Some(lo), Some(hi)), _) if lo > hi => return None,
+            ((Some(lo), _), 1..) | ((Some(lo), None), 0) => lo,
+            ((_, Some(hi)), ..=0) => hi,
+            _ => panic!("Token cost is unbounded"),
+        };
+        let (na, nb) = (na0 + t * step_a, nb0 + t * step_b);
+        Some((ca * na + cb * nb) as i64)
     }
 
     fn translate(&self, tr: i64) -> Self {
@@ -89,21 +172,19 @@ fn parse(input: String) -> Vec<Machine> {
     input.split("\n\n").map(Into::into).collect()
 }
 
-fn part1(input: String) {
-    let machines = parse(input);
-    let sum = machines.iter().map(|m| m.tokens_100()).sum::<i64>();
-    println!("{sum}");
+fn solve(input: String, translation: i64, pricing: &Pricing) -> i64 {
+    parse(input)
+        .iter()
+        .filter_map(|m| m.translate(translation).tokens(pricing))
+        .sum()
 }
 
-const TRANSLATION: i64 = 10000000000000;
+fn part1(input: String) {
+    println!("{}", solve(input, 0, &PART1));
+}
 
 fn part2(input: String) {
-    let machines = parse(input);
-    let sum = machines
-        .iter()
-        .map(|m| m.translate(TRANSLATION).tokens_inv())
-        .sum::<i64>();
-    println!("{sum}");
+    println!("{}", solve(input, 10000000000000, &PART2));
 }
 
 util::aoc_main!();
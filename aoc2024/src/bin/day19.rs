@@ -1,45 +1,153 @@
-use regex::Regex;
 use rustc_hash::FxHashMap;
 
-fn parse(input: &str) -> (Vec<&str>, Vec<&str>) {
-    let (towels, designs) = input.split_once("\n\n").unwrap();
-    (towels.split(", ").collect(), designs.lines().collect())
+/// Number of decompositions listed per design in verbose mode
+const MAX_LISTED: usize = 3;
+
+#[derive(Default)]
+struct Node {
+    children: FxHashMap<u8, usize>,
+    /// Index of the towel ending at this node
+    towel: Option<usize>,
 }
 
-fn part1(input: String) {
-    let (towels, designs) = parse(&input);
-    let pat = format!("^({})*$", towels.join("|"));
-    let re = Regex::new(&pat).unwrap();
-    let count = designs.iter().filter(|d| re.is_match(d)).count();
-    println!("{count}");
+/// Trie of all towel patterns, used to find every towel matching at a
+/// position of a design in a single walk.
+struct Towels<'a> {
+    patterns: Vec<&'a str>,
+    nodes: Vec<Node>,
+}
+
+impl<'a> Towels<'a> {
+    fn new(patterns: Vec<&'a str>) -> Self {
+        let mut nodes = vec![Node::default()];
+        for (i, p) in patterns.iter().enumerate() {
+            let mut cur = 0;
+            for b in p.bytes() {
+                cur = match nodes[cur].children.get(&b) {
+                    Some(&next) => next,
+                    None => {
+                        nodes.push(Node::default());
+                        let next = nodes.len() - 1;
+                        nodes[cur].children.insert(b, next);
+                        next
+                    }
+                };
+            }
+            nodes[cur].towel = Some(i);
+        }
+        Towels { patterns, nodes }
+    }
+
+    /// All towels matching `design` at `start`, as (end, towel index), shortest first.
+    fn matches(&self, design: &[u8], start: usize) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+        let mut cur = 0;
+        for (end, b) in design.iter().enumerate().skip(start) {
+            match self.nodes[cur].children.get(b) {
+                Some(&next) => cur = next,
+                None => break,
+            }
+            if let Some(t) = self.nodes[cur].towel {
+                found.push((end + 1, t));
+            }
+        }
+        found
+    }
+
+    /// The number of ways to arrange towels for each suffix of `design`,
+    /// without using `exclude` for the whole design.
+    fn arrangements(&self, design: &[u8], exclude: Option<usize>) -> Vec<u64> {
+        let mut ways = vec![0; design.len() + 1];
+        ways[design.len()] = 1;
+        for start in (0..design.len()).rev() {
+            ways[start] = self
+                .matches(design, start)
+                .into_iter()
+                .filter(|&(end, t)| !(start == 0 && end == design.len() && exclude == Some(t)))
+                .map(|(end, _)| ways[end])
+                .sum();
+        }
+        ways
+    }
+
+    fn n_arrangements(&self, design: &str) -> u64 {
+        self.arrangements(design.as_bytes(), None)[0]
+    }
+
+    /// Up to `k` arrangements of `design`, choosing shorter towels first.
+    fn decompositions(&self, design: &str, k: usize) -> Vec<Vec<&'a str>> {
+        let design = design.as_bytes();
+        let ways = self.arrangements(design, None);
+        let mut found = Vec::new();
+        let mut stack = vec![(0, Vec::new())];
+        while let Some((start, used)) = stack.pop() {
+            if found.len() == k {
+                break;
+            }
+            if start == design.len() {
+                found.push(used);
+                continue;
+            }
+            // Only continue where the rest can be completed,
+            // so no branch is ever a dead end.
+            for (end, t) in self.matches(design, start).into_iter().rev() {
+                if ways[end] > 0 {
+                    let mut next = used.clone();
+                    next.push(self.patterns[t]);
+                    stack.push((end, next));
+                }
+            }
+        }
+        found
+    }
+
+    /// Towels that can be built from other towels
+    fn redundant(&self) -> Vec<&'a str> {
+        self.patterns
+            .iter()
+            .enumerate()
+            .filter(|&(i, p)| self.arrangements(p.as_bytes(), Some(i))[0] > 0)
+            .map(|(_, p)| *p)
+            .collect()
+    }
+}
+
+fn parse(input: &str) -> (Towels<'_>, Vec<&str>) {
+    let (towels, designs) = input.split_once("\n\n").unwrap();
+    (
+        Towels::new(towels.split(", ").collect()),
+        designs.lines().collect(),
+    )
 }
 
-fn n_arrangements<'a>(
-    design: &'a str,
-    towels: &[&str],
-    cache: &mut FxHashMap<&'a str, u64>,
-) -> u64 {
-    if design.is_empty() {
-        return 1;
+fn report(towels: &Towels, designs: &[&str]) {
+    println!("Redundant towels: {}", towels.redundant().join(", "));
+    for d in designs {
+        println!("{d}: {}", towels.n_arrangements(d));
+        for arrangement in towels.decompositions(d, MAX_LISTED) {
+            println!("    {}", arrangement.join(" "));
+        }
     }
-    if let Some(n) = cache.get(design) {
-        return *n;
+}
+
+fn part1(input: String) {
+    let (towels, designs) = parse(&input);
+    if util::verbose() {
+        report(&towels, &designs);
     }
-    let n = towels
+    let count = designs
         .iter()
-        .filter(|t| design.starts_with(*t))
-        .map(|t| n_arrangements(&design[t.len()..], towels, cache))
-        .sum();
-    cache.insert(design, n);
-    n
+        .filter(|d| towels.n_arrangements(d) > 0)
+        .count();
+    println!("{count}");
 }
 
 fn part2(input: String) {
     let (towels, designs) = parse(&input);
-    let sum: u64 = designs
-        .iter()
-        .map(|d| n_arrangements(d, &towels, &mut FxHashMap::default()))
-        .sum();
+    if util::verbose() {
+        report(&towels, &designs);
+    }
+    let sum: u64 = designs.iter().map(|d| towels.n_arrangements(d)).sum();
     println!("{sum}");
 }
 
@@ -1,30 +1,98 @@
+use std::ops::RangeInclusive;
+
+const STEPS: RangeInclusive<i32> = 1..=3;
+
 fn parse(input: String) -> Vec<Vec<i32>> {
-    input.lines()
+    input
+        .lines()
         .map(|l| l.split_whitespace().map(|w| w.parse().unwrap()).collect())
         .collect()
 }
 
-fn is_safe(report: impl DoubleEndedIterator<Item=i32> + Clone) -> bool {
-    let safety = |a: &i32, b: &i32| (1..=3).contains(&(b - a));
-    report.clone().is_sorted_by(safety) || report.rev().is_sorted_by(safety)
+/// Fewest levels to remove so that each remaining level rises above the
+/// previous one by an amount in `steps`, if at most `tolerance` are needed.
+///
+/// `removed[i]` is the fewest removals before level `i` in a valid sequence
+/// that keeps level `i`. Since no more than `tolerance` levels can be skipped
+/// between two kept ones, only that many predecessors need to be checked,
+/// giving O(n * tolerance) time.
+fn removals_rising(
+    report: impl Iterator<Item = i32>,
+    tolerance: usize,
+    steps: &RangeInclusive<i32>,
+) -> Option<Vec<usize>> {
+    let levels: Vec<i32> = report.collect();
+    let n = levels.len();
+    let mut removed = vec![0; n];
+    let mut pred = vec![None; n];
+    for i in 0..n {
+        // Remove everything before
+        removed[i] = i;
+        for j in i.saturating_sub(tolerance + 1)..i {
+            let cost = removed[j] + (i - j - 1);
+            if cost < removed[i] && steps.contains(&(levels[i] - levels[j])) {
+                removed[i] = cost;
+                pred[i] = Some(j);
+            }
+        }
+    }
+    // Remove everything after the last kept level
+    let (last, total) = (0..n)
+        .map(|i| (i, removed[i] + n - 1 - i))
+        .min_by_key(|&(_, r)| r)?;
+    if total > tolerance {
+        return None;
+    }
+    let mut keep = vec![false; n];
+    let mut cur = Some(last);
+    while let Some(i) = cur {
+        keep[i] = true;
+        cur = pred[i];
+    }
+    Some((0..n).filter(|&i| !keep[i]).collect())
 }
 
-fn part1(input: String) {
+/// Indices of the levels to remove to make the report safe, either rising
+/// or falling, with at most `tolerance` removed levels.
+fn dampen(report: &[i32], tolerance: usize, steps: &RangeInclusive<i32>) -> Option<Vec<usize>> {
+    if report.is_empty() {
+        return Some(Vec::new());
+    }
+    let rising = removals_rising(report.iter().copied(), tolerance, steps);
+    let falling = removals_rising(report.iter().map(|n| -n), tolerance, steps);
+    match (rising, falling) {
+        (Some(r), Some(f)) => Some(if f.len() < r.len() { f } else { r }),
+        (r, f) => r.or(f),
+    }
+}
+
+fn solve(input: String, tolerance: usize) -> usize {
     let reports = parse(input);
-    let safe = reports.iter().filter(|r| is_safe(r.iter().copied())).count();
-    println!("{safe}");
+    let verbose = util::verbose();
+    let mut safe = 0;
+    for r in &reports {
+        let removed = dampen(r, tolerance, &STEPS);
+        if verbose {
+            let levels: Vec<String> = r.iter().map(i32::to_string).collect();
+            match &removed {
+                None => println!("{}: Unsafe", levels.join(" ")),
+                Some(v) if v.is_empty() => println!("{}: Safe", levels.join(" ")),
+                Some(v) => println!("{}: Safe without levels at {v:?}", levels.join(" ")),
+            }
+        }
+        if removed.is_some() {
+            safe += 1;
+        }
+    }
+    safe
 }
 
-fn is_safe2(report: &[i32]) -> bool {
-    (0..report.len()).any(|i| {  // Try with each element removed
-        is_safe(report.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, n)| *n))
-    })
+fn part1(input: String) {
+    println!("{}", solve(input, 0));
 }
 
 fn part2(input: String) {
-    let reports = parse(input);
-    let safe = reports.iter().filter(|r| is_safe2(r)).count();
-    println!("{safe}");
+    println!("{}", solve(input, 1));
 }
 
 util::aoc_main!();
@@ -1,129 +1,180 @@
-use euclid::{default::*, point2, vec2};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use euclid::{default::*, vec2};
 use rustc_hash::FxHashMap;
-use std::iter;
-
-type Move = Option<Vector2D<i32>>;
-
-const KEYPAD_GAP: Point2D<i32> = point2(0, 3);
-const DPAD_GAP: Point2D<i32> = point2(0, 0);
-
-fn keypad_pos(n: u8) -> Point2D<i32> {
-    match n {
-        b'7' => point2(0, 0),
-        b'8' => point2(1, 0),
-        b'9' => point2(2, 0),
-        b'4' => point2(0, 1),
-        b'5' => point2(1, 1),
-        b'6' => point2(2, 1),
-        b'1' => point2(0, 2),
-        b'2' => point2(1, 2),
-        b'3' => point2(2, 2),
-        b'0' => point2(1, 3),
-        b'A' => point2(2, 3),
-        other => panic!("Invalid keypad symbol {other}"),
-    }
+
+/// Marks a position in a layout that has no button
+const GAP: u8 = b'.';
+const NUMPAD: &str = "789\n456\n123\n.0A";
+const DPAD: &str = ".^A\n<v>";
+/// Longest sequence of button presses that is still printed in verbose mode
+const MAX_PRINT_LEN: u64 = 1000;
+
+const DIRS: [(u8, Vector2D<i32>); 4] = [
+    (b'^', vec2(0, -1)),
+    (b'v', vec2(0, 1)),
+    (b'<', vec2(-1, 0)),
+    (b'>', vec2(1, 0)),
+];
+
+struct Keypad {
+    keys: FxHashMap<u8, Point2D<i32>>,
+    buttons: FxHashMap<Point2D<i32>, u8>,
 }
 
-// `None` is used for A
-fn dpad_pos(d: Move) -> Point2D<i32> {
-    match d {
-        Some(Vector2D { x: 0, y: -1, .. }) => point2(1, 0),
-        None => point2(2, 0),
-        Some(Vector2D { x: -1, y: 0, .. }) => point2(0, 1),
-        Some(Vector2D { x: 0, y: 1, .. }) => point2(1, 1),
-        Some(Vector2D { x: 1, y: 0, .. }) => point2(2, 1),
-        other => panic!("Invalid dpad symbol {other:?}"),
+impl Keypad {
+    fn parse(layout: &str) -> Self {
+        let mut keys = FxHashMap::default();
+        let mut buttons = FxHashMap::default();
+        for (y, l) in layout.lines().enumerate() {
+            for (x, b) in l.bytes().enumerate() {
+                if b != GAP {
+                    let pos = Point2D::new(x, y).to_i32();
+                    assert!(keys.insert(b, pos).is_none(), "Duplicate key {}", b as char);
+                    buttons.insert(pos, b);
+                }
+            }
+        }
+        assert!(keys.contains_key(&b'A'), "Keypad has no A button");
+        Keypad { keys, buttons }
     }
 }
 
-fn moves_for_diff(diff: Vector2D<i32>, pos: Point2D<i32>, gap: Point2D<i32>) -> Vec<Vec<Move>> {
-    let horizontal = iter::repeat_n(
-        Some(vec2(diff.x.signum(), 0)),
-        diff.x.unsigned_abs() as usize,
-    );
-    let vertical = iter::repeat_n(
-        Some(vec2(0, diff.y.signum())),
-        diff.y.unsigned_abs() as usize,
-    );
-    if pos + vec2(diff.x, 0) == gap {
-        // Must not move horizontal first, or we hit the gap
-        vec![vertical.chain(horizontal).chain(iter::once(None)).collect()]
-    } else if pos + vec2(0, diff.y) == gap {
-        vec![horizontal.chain(vertical).chain(iter::once(None)).collect()]
-    } else {
-        // Try both horizontal first and vertical first
-        vec![
-            horizontal
-                .clone()
-                .chain(vertical.clone())
-                .chain(iter::once(None))
-                .collect(),
-            vertical.chain(horizontal).chain(iter::once(None)).collect(),
-        ]
-    }
+/// Minimal presses on the controlling keypad to move from one key to another
+/// and press it, together with the keys pressed on the controlling keypad.
+type Moves = FxHashMap<(u8, u8), (u64, Vec<u8>)>;
+
+/// A chain of keypads, where each robot typing on one keypad is directed
+/// through the next one. The last keypad is pressed directly.
+struct Chain {
+    levels: Vec<Moves>,
 }
 
-fn dpad_sequence_len(
-    start: Move,
-    end: Move,
-    rounds: u32,
-    cache: &mut FxHashMap<(Move, Move, u32), u64>,
-) -> u64 {
-    if rounds == 0 {
-        return 1;
+impl Chain {
+    fn new(keypads: &[Keypad]) -> Self {
+        let last = keypads.last().expect("Empty keypad chain");
+        let direct = last
+            .keys
+            .keys()
+            .flat_map(|&from| last.keys.keys().map(move |&to| ((from, to), (1, vec![to]))))
+            .collect();
+        let mut levels = vec![direct];
+        for keypad in keypads.iter().rev().skip(1) {
+            let moves = Self::optimal_moves(keypad, levels.last().unwrap());
+            levels.push(moves);
+        }
+        levels.reverse();
+        Chain { levels }
     }
-    if let Some(len) = cache.get(&(start, end, rounds)) {
-        return *len;
+
+    /// Find the cheapest way between every pair of keys of `keypad`. The
+    /// state of the search is the position on `keypad` together with the
+    /// last key pressed on the controlling keypad, where that robot's arm
+    /// currently rests.
+    fn optimal_moves(keypad: &Keypad, control: &Moves) -> Moves {
+        let mut moves = FxHashMap::default();
+        for (&from, &start) in &keypad.keys {
+            type Node = (Point2D<i32>, u8);
+            let mut dist: FxHashMap<Node, u64> = FxHashMap::default();
+            let mut pred: FxHashMap<Node, Node> = FxHashMap::default();
+            let mut heap = BinaryHeap::new();
+            dist.insert((start, b'A'), 0);
+            heap.push(Reverse((0, start.x, start.y, b'A')));
+            while let Some(Reverse((d, x, y, prev))) = heap.pop() {
+                let pos = Point2D::new(x, y);
+                if d > dist[&(pos, prev)] {
+                    continue;
+                }
+                for (key, dir) in DIRS {
+                    let next = pos + dir;
+                    let Some(&(cost, _)) = control.get(&(prev, key)) else {
+                        continue;
+                    };
+                    if !keypad.buttons.contains_key(&next) {
+                        continue;
+                    }
+                    let new_dist = d + cost;
+                    if dist.get(&(next, key)).is_none_or(|&old| new_dist < old) {
+                        dist.insert((next, key), new_dist);
+                        pred.insert((next, key), (pos, prev));
+                        heap.push(Reverse((new_dist, next.x, next.y, key)));
+                    }
+                }
+            }
+            for (&to, &end) in &keypad.keys {
+                // Finally press A on the controller to push the button
+                let best = dist
+                    .iter()
+                    .filter(|((pos, _), _)| *pos == end)
+                    .filter_map(|(&(pos, prev), &d)| {
+                        let (cost, _) = control.get(&(prev, b'A'))?;
+                        Some((d + cost, pos, prev))
+                    })
+                    .min_by_key(|&(total, ..)| total);
+                let Some((total, pos, prev)) = best else {
+                    continue;
+                };
+                let mut pressed = vec![b'A'];
+                let mut node = (pos, prev);
+                while let Some(&before) = pred.get(&node) {
+                    pressed.push(node.1);
+                    node = before;
+                }
+                pressed.reverse();
+                moves.insert((from, to), (total, pressed));
+            }
+        }
+        moves
     }
-    let start_pos = dpad_pos(start);
-    let end_pos = dpad_pos(end);
-    let diff = end_pos - start_pos;
-    let possible_paths = moves_for_diff(diff, start_pos, DPAD_GAP);
-    let shortest_sequence = possible_paths
-        .iter()
-        .map(|moves| {
-            moves
-                .iter()
-                .fold((0, None), |(cost, prev), &m| {
-                    (cost + dpad_sequence_len(prev, m, rounds - 1, cache), m)
-                })
-                .0
-        })
-        .min()
-        .unwrap();
-    cache.insert((start, end, rounds), shortest_sequence);
-    shortest_sequence
-}
 
-fn keypad_sequence_len(start: u8, end: u8, rounds: u32) -> u64 {
-    let start_pos = keypad_pos(start);
-    let end_pos = keypad_pos(end);
-    let diff = end_pos - start_pos;
-    let possible_paths = moves_for_diff(diff, start_pos, KEYPAD_GAP);
-    let mut cache = FxHashMap::default();
-    possible_paths
-        .iter()
-        .map(|moves| {
-            moves
-                .iter()
-                .fold((0, None), |(cost, prev), &m| {
-                    (cost + dpad_sequence_len(prev, m, rounds, &mut cache), m)
-                })
-                .0
-        })
-        .min()
-        .unwrap()
-}
+    fn move_len(&self, level: usize, from: u8, to: u8) -> u64 {
+        self.levels[level]
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("Can't reach {} from {}", to as char, from as char))
+            .0
+    }
 
-fn solve(input: &str, rounds: u32) -> u64 {
-    let mut sum: u64 = 0;
-    for l in input.lines() {
+    /// Number of presses on the last keypad needed to type `code` on the first.
+    fn sequence_len(&self, code: &[u8]) -> u64 {
         let mut prev = b'A';
         let mut len = 0;
-        for b in l.bytes() {
-            len += keypad_sequence_len(prev, b, rounds);
+        for &b in code {
+            len += self.move_len(0, prev, b);
+            prev = b;
+        }
+        len
+    }
+
+    /// The buttons pressed on the last keypad to type `code` on the keypad at `level`.
+    fn sequence(&self, level: usize, code: &[u8]) -> Vec<u8> {
+        let mut prev = b'A';
+        let mut seq = Vec::new();
+        for &b in code {
+            let (_, pressed) = &self.levels[level][&(prev, b)];
+            if level + 1 == self.levels.len() {
+                seq.extend(pressed);
+            } else {
+                seq.extend(self.sequence(level + 1, pressed));
+            }
             prev = b;
         }
+        seq
+    }
+}
+
+fn solve(input: &str, robots: usize) -> u64 {
+    let mut keypads = vec![Keypad::parse(NUMPAD)];
+    // One directional keypad for each robot, plus the one we press ourselves
+    keypads.extend((0..=robots).map(|_| Keypad::parse(DPAD)));
+    let chain = Chain::new(&keypads);
+    let mut sum: u64 = 0;
+    for l in input.lines() {
+        let len = chain.sequence_len(l.as_bytes());
+        if util::verbose() && len <= MAX_PRINT_LEN {
+            let seq = chain.sequence(0, l.as_bytes());
+            println!("{l}: {}", String::from_utf8(seq).unwrap());
+        }
         let code_n: u64 = l.strip_suffix('A').unwrap().parse().unwrap();
         sum += code_n * len;
     }
@@ -1,57 +1,143 @@
+use rayon::prelude::*;
+
+/// Number of buyers simulated at once
+const LANES: usize = 16;
+const ROUNDS: usize = 2000;
+const N_SEQUENCES: usize = 19usize.pow(4);
+
 fn step(n: u32) -> u32 {
     let a = (n ^ (n << 6)) % (1 << 24);
     let b = a ^ (a >> 5);
     (b ^ (b << 11)) % (1 << 24)
 }
 
+/// Written over a fixed-size array, this gets vectorized by the compiler.
+fn step_lanes(lanes: &mut [u32; LANES]) {
+    for n in lanes.iter_mut() {
+        *n = step(*n);
+    }
+}
+
+/// Run up to `LANES` buyers side by side, calling `visit` with the initial
+/// secrets and after each step. Unused lanes are filled with zeros.
+fn simulate(buyers: &[u32], mut visit: impl FnMut(&[u32; LANES])) {
+    let mut lanes = [0; LANES];
+    lanes[..buyers.len()].copy_from_slice(buyers);
+    visit(&lanes);
+    for _ in 0..ROUNDS {
+        step_lanes(&mut lanes);
+        visit(&lanes);
+    }
+}
+
+fn parse(input: &str) -> Vec<u32> {
+    input.lines().map(|l| l.parse().unwrap()).collect()
+}
+
 fn part1(input: String) {
-    let sum = input
-        .lines()
-        .map(|l| {
-            let n = l.parse().unwrap();
-            (0..2000).fold(n, |acc, _| step(acc)) as u64
+    let sum = parse(&input)
+        .par_chunks(LANES)
+        .map(|buyers| {
+            let mut last = [0; LANES];
+            simulate(buyers, |lanes| last = *lanes);
+            // More than 2¹⁰ 24-bit numbers requires 35 bits
+            last.iter().map(|&n| n as u64).sum::<u64>()
         })
-        // More than 2¹⁰ 24-bit numbers requires 35 bits
         .sum::<u64>();
     println!("{sum}");
 }
 
-const N_SEQUENCES: usize = 19usize.pow(4);
+/// Prices of a group of buyers, indexed by round and then by lane
+fn prices(buyers: &[u32]) -> Vec<[u8; LANES]> {
+    let mut prices = Vec::with_capacity(ROUNDS + 1);
+    simulate(buyers, |lanes| prices.push(lanes.map(|n| (n % 10) as u8)));
+    prices
+}
 
-fn sequence_key(sequence: &[i8]) -> usize {
-    sequence
-        .iter()
-        .enumerate()
-        .map(|(i, x)| (x + 9) as usize * 19usize.pow(i as u32))
-        .sum()
+/// Call `visit` with the key of each sequence of 4 changes and the price
+/// after it, for the buyer in `lane`.
+fn sequences(prices: &[[u8; LANES]], lane: usize, mut visit: impl FnMut(usize, u8)) {
+    let mut key = 0;
+    for (i, w) in prices.windows(2).enumerate() {
+        let diff = w[1][lane] as usize + 9 - w[0][lane] as usize;
+        // Shift in the latest change, dropping the oldest one
+        key = (key * 19 + diff) % N_SEQUENCES;
+        if i >= 3 {
+            visit(key, w[1][lane]);
+        }
+    }
+}
+
+fn decode(key: usize) -> [i8; 4] {
+    let mut changes = [0; 4];
+    for (i, c) in changes.iter_mut().enumerate() {
+        *c = (key / 19usize.pow(3 - i as u32) % 19) as i8 - 9;
+    }
+    changes
 }
 
 fn part2(input: String) {
-    // Table for collecting the amount of bananas for every possible sequence
-    let mut table = vec![0; N_SEQUENCES];
-    // Mark the sequences we encountered in a round to ensure that only the first occurence is used
-    let mut seen = vec![false; N_SEQUENCES];
-    for l in input.lines() {
-        let n = l.parse().unwrap();
-        let (diffs, prices): (Vec<i8>, Vec<u8>) = (0..2000)
-            .scan(n, |acc, _| {
-                let next = step(*acc);
-                let diff = (next % 10) as i8 - (*acc % 10) as i8;
-                *acc = next;
-                Some((diff, (next % 10) as u8))
+    let buyers = parse(&input);
+    // Each thread collects the bananas for every possible sequence in its own
+    // table. The second table records the last buyer that encountered each
+    // sequence, because only the first occurrence per buyer counts.
+    let table = buyers
+        .par_chunks(LANES)
+        .enumerate()
+        .fold(
+            || (vec![0u32; N_SEQUENCES], vec![usize::MAX; N_SEQUENCES]),
+            |(mut table, mut seen), (chunk, group)| {
+                let prices = prices(group);
+                for lane in 0..group.len() {
+                    let buyer = chunk * LANES + lane;
+                    sequences(&prices, lane, |key, price| {
+                        if seen[key] != buyer {
+                            seen[key] = buyer;
+                            table[key] += price as u32;
+                        }
+                    });
+                }
+                (table, seen)
+            },
+        )
+        .map(|(table, _)| table)
+        .reduce(
+            || vec![0; N_SEQUENCES],
+            |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(x, y)| *x += y);
+                a
+            },
+        );
+    let (best, bananas) = table
+        .iter()
+        .enumerate()
+        .max_by_key(|&(_, bananas)| bananas)
+        .unwrap();
+
+    if util::verbose() {
+        println!("Sequence: {:?}", decode(best));
+        // The price each buyer sells for, or 0 if the sequence never occurs
+        let sold: Vec<u8> = buyers
+            .par_chunks(LANES)
+            .flat_map_iter(|group| {
+                let prices = prices(group);
+                (0..group.len())
+                    .map(|lane| {
+                        let mut sold = None;
+                        sequences(&prices, lane, |key, price| {
+                            if key == best && sold.is_none() {
+                                sold = Some(price);
+                            }
+                        });
+                        sold.unwrap_or(0)
+                    })
+                    .collect::<Vec<_>>()
             })
-            .unzip();
-        for (window, price) in diffs.windows(4).zip(prices.iter().skip(3)) {
-            let key = sequence_key(window);
-            if !seen[key] {
-                seen[key] = true;
-                table[key] += *price as u32;
-            }
+            .collect();
+        for (secret, price) in buyers.iter().zip(sold) {
+            println!("{secret}: {price}");
         }
-        // Reset seen sequences for next round
-        seen.fill(false);
     }
-    let bananas = table.iter().max().unwrap();
     println!("{bananas}");
 }
 
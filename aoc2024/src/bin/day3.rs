@@ -1,30 +1,232 @@
-use regex::{Regex, Captures};
+use regex::Regex;
 
-fn mul_cap(cap: Captures) -> i32 {
-    let a = cap.get(1).unwrap().as_str().parse::<i32>().unwrap();
-    let b = cap.get(2).unwrap().as_str().parse::<i32>().unwrap();
-    a * b
+/// Input is fed to the tokenizer in pieces of this size
+const CHUNK_SIZE: usize = 4096;
+/// Maximum number of digits in an argument
+const MAX_DIGITS: usize = 3;
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Mul,
+    Add,
+    Sub,
+    Do,
+    Dont,
 }
 
-fn part1(input: String) {
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)").unwrap();
-    let res = re.captures_iter(&input).map(mul_cap).sum::<i32>();
-    println!("{res}");
+struct Spec {
+    name: &'static str,
+    op: Op,
+    n_args: usize,
 }
 
-fn part2(input: String) {
-    let re = Regex::new(r"do\(\)|don't\(\)|mul\((\d{1,3}),(\d{1,3})\)").unwrap();
+const MUL: Spec = Spec {
+    name: "mul",
+    op: Op::Mul,
+    n_args: 2,
+};
+const DO: Spec = Spec {
+    name: "do",
+    op: Op::Do,
+    n_args: 0,
+};
+const DONT: Spec = Spec {
+    name: "don't",
+    op: Op::Dont,
+    n_args: 0,
+};
+const ADD: Spec = Spec {
+    name: "add",
+    op: Op::Add,
+    n_args: 2,
+};
+const SUB: Spec = Spec {
+    name: "sub",
+    op: Op::Sub,
+    n_args: 2,
+};
+
+const PART1: &[Spec] = &[MUL];
+const PART2: &[Spec] = &[MUL, DO, DONT];
+const EXTENDED: &[Spec] = &[MUL, DO, DONT, ADD, SUB];
+
+#[derive(Debug)]
+struct Instruction {
+    op: Op,
+    args: Vec<u32>,
+    /// Byte offset of the instruction in the input
+    offset: usize,
+}
+
+enum Parse {
+    /// Instruction with its arguments and length in bytes
+    Match(Op, Vec<u32>, usize),
+    Fail,
+    /// Input ends before the instruction is complete
+    Incomplete,
+}
+
+impl Spec {
+    /// Try to read this instruction from the start of `s`, like `name(1,23)`.
+    fn parse(&self, s: &[u8]) -> Parse {
+        let head = [self.name.as_bytes(), b"("].concat();
+        let n = head.len().min(s.len());
+        if s[..n] != head[..n] {
+            return Parse::Fail;
+        }
+        if s.len() < head.len() {
+            return Parse::Incomplete;
+        }
+        let mut pos = head.len();
+        let mut args = Vec::with_capacity(self.n_args);
+        for i in 0..self.n_args {
+            if i > 0 {
+                match s.get(pos) {
+                    None => return Parse::Incomplete,
+                    Some(b',') => pos += 1,
+                    Some(_) => return Parse::Fail,
+                }
+            }
+            let digits = s[pos..].iter().take_while(|b| b.is_ascii_digit()).count();
+            if digits == 0 && pos == s.len() {
+                return Parse::Incomplete;
+            }
+            if digits == 0 || digits > MAX_DIGITS {
+                return Parse::Fail;
+            }
+            let arg = std::str::from_utf8(&s[pos..pos + digits]).unwrap();
+            args.push(arg.parse().unwrap());
+            pos += digits;
+        }
+        match s.get(pos) {
+            // More digits could follow
+            None => Parse::Incomplete,
+            Some(b')') => Parse::Match(self.op, args, pos + 1),
+            Some(_) => Parse::Fail,
+        }
+    }
+}
+
+/// Recognizes instructions in a stream of bytes. Only the bytes of a
+/// possibly unfinished instruction are kept between chunks.
+struct Tokenizer<'a> {
+    specs: &'a [Spec],
+    pending: Vec<u8>,
+    /// Offset of the first pending byte in the stream
+    offset: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(specs: &'a [Spec]) -> Self {
+        Tokenizer {
+            specs,
+            pending: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8], out: &mut Vec<Instruction>) {
+        self.pending.extend_from_slice(chunk);
+        self.scan(false, out);
+    }
+
+    fn finish(mut self, out: &mut Vec<Instruction>) {
+        self.scan(true, out);
+    }
+
+    fn scan(&mut self, at_end: bool, out: &mut Vec<Instruction>) {
+        let mut start = 0;
+        while start < self.pending.len() {
+            let rest = &self.pending[start..];
+            let mut incomplete = false;
+            let mut matched = None;
+            for spec in self.specs {
+                match spec.parse(rest) {
+                    Parse::Match(op, args, len) => {
+                        matched = Some((op, args, len));
+                        break;
+                    }
+                    Parse::Incomplete => incomplete = true,
+                    Parse::Fail => {}
+                }
+            }
+            if let Some((op, args, len)) = matched {
+                let offset = self.offset + start;
+                out.push(Instruction { op, args, offset });
+                start += len;
+            } else if incomplete && !at_end {
+                // Wait for the next chunk
+                break;
+            } else {
+                start += 1;
+            }
+        }
+        self.pending.drain(..start);
+        self.offset += start;
+    }
+}
+
+fn tokenize(input: &str, specs: &[Spec]) -> Vec<Instruction> {
+    let mut tokenizer = Tokenizer::new(specs);
+    let mut instructions = Vec::new();
+    for chunk in input.as_bytes().chunks(CHUNK_SIZE) {
+        tokenizer.feed(chunk, &mut instructions);
+    }
+    tokenizer.finish(&mut instructions);
+    instructions
+}
+
+fn run(instructions: &[Instruction]) -> i64 {
     let mut enabled = true;
     let mut res = 0;
-    for cap in re.captures_iter(&input) {
-        match cap.get(0).unwrap().as_str() {
-            "do()" => enabled = true,
-            "don't()" => enabled = false,
-            _ if enabled => res += mul_cap(cap),
-            _ => {}
+    for instr in instructions {
+        let arg = |i: usize| instr.args[i] as i64;
+        match instr.op {
+            Op::Do => enabled = true,
+            Op::Dont => enabled = false,
+            _ if !enabled => {}
+            Op::Mul => res += arg(0) * arg(1),
+            Op::Add => res += arg(0) + arg(1),
+            Op::Sub => res += arg(0) - arg(1),
+        }
+    }
+    res
+}
+
+/// Equivalent regular expression, used to cross-check the tokenizer
+fn regex(specs: &[Spec]) -> Regex {
+    let alternatives: Vec<String> = specs
+        .iter()
+        .map(|s| {
+            let args = vec![format!(r"\d{{1,{MAX_DIGITS}}}"); s.n_args].join(",");
+            format!(r"{}\({args}\)", regex::escape(s.name))
+        })
+        .collect();
+    Regex::new(&alternatives.join("|")).unwrap()
+}
+
+fn solve(input: &str, specs: &[Spec]) -> i64 {
+    let instructions = tokenize(input, specs);
+    if util::verbose() {
+        for instr in &instructions {
+            println!("{:>6}: {:?} {:?}", instr.offset, instr.op, instr.args);
+        }
+        let regex_offsets: Vec<usize> = regex(specs).find_iter(input).map(|m| m.start()).collect();
+        let offsets: Vec<usize> = instructions.iter().map(|i| i.offset).collect();
+        if offsets != regex_offsets {
+            println!("Tokenizer and regex disagree");
         }
+        println!("With add and sub: {}", run(&tokenize(input, EXTENDED)));
     }
-    println!("{res}");
+    run(&instructions)
+}
+
+fn part1(input: String) {
+    println!("{}", solve(&input, PART1));
+}
+
+fn part2(input: String) {
+    println!("{}", solve(&input, PART2));
 }
 
 util::aoc_main!();
@@ -1,64 +1,167 @@
-use euclid::{vec2, default::*};
+use euclid::{default::*, vec2};
 
-fn count_xmas(grid: &[&[u8]], pos: (usize, usize)) -> u32 {
-    if grid[pos.1][pos.0] != b'X' {
-        return 0
+const DIRS: [Vector2D<i32>; 8] = [
+    vec2(1, 0),
+    vec2(-1, 0),
+    vec2(0, 1),
+    vec2(0, -1),
+    vec2(1, 1),
+    vec2(1, -1),
+    vec2(-1, 1),
+    vec2(-1, -1),
+];
+/// Matches any letter in a shape template
+const WILDCARD: u8 = b'.';
+const X_MAS: &str = "M.S\n.A.\nM.S";
+
+struct Grid<'a> {
+    rows: Vec<&'a [u8]>,
+    bounds: Rect<i32>,
+}
+
+struct WordMatch<'a> {
+    word: &'a str,
+    start: Point2D<i32>,
+    dir: Vector2D<i32>,
+}
+
+impl WordMatch<'_> {
+    fn cells(&self) -> impl Iterator<Item = Point2D<i32>> + '_ {
+        (0..self.word.len() as i32).map(|i| self.start + self.dir * i)
     }
+}
+
+/// Letters of a shape relative to its top left corner, leaving out wildcards
+#[derive(PartialEq)]
+struct Template(Vec<(Vector2D<i32>, u8)>);
 
-    let bounds = Rect::new(Point2D::origin(), Size2D::new(grid[0].len() as i32, grid.len() as i32));
-    const DIRS: [Vector2D<i32>; 8] = [
-        vec2(1, 0), vec2(-1, 0), vec2(0, 1), vec2(0, -1),
-        vec2(1, 1), vec2(1, -1), vec2(-1, 1), vec2(-1, -1),
-    ];
-    let mut count = 0;
-    for dir in DIRS {
-        let mut cur = Point2D::from(pos).to_i32();
-        let mut found = true;
-        for letter in [b'M', b'A', b'S'] {
-            cur += dir;
-            if !bounds.contains(cur) || grid[cur.y as usize][cur.x as usize] != letter {
-                found = false;
-                break
+impl Template {
+    fn parse(s: &str) -> Self {
+        let mut cells = Vec::new();
+        for (y, l) in s.lines().enumerate() {
+            for (x, b) in l.bytes().enumerate().filter(|&(_, b)| b != WILDCARD) {
+                cells.push((vec2(x, y).to_i32(), b));
             }
         }
-        if found {
-            count += 1;
+        Self::normalized(cells)
+    }
+
+    /// Move to non-negative offsets starting at 0, in a canonical order
+    fn normalized(mut cells: Vec<(Vector2D<i32>, u8)>) -> Self {
+        let min_x = cells.iter().map(|(v, _)| v.x).min().unwrap_or(0);
+        let min_y = cells.iter().map(|(v, _)| v.y).min().unwrap_or(0);
+        for (v, _) in &mut cells {
+            *v -= vec2(min_x, min_y);
         }
+        cells.sort_unstable_by_key(|&(v, _)| (v.y, v.x));
+        Template(cells)
     }
-    count
-}
 
-fn part1(input: String) {
-    let grid = input.lines().map(|l| l.as_bytes()).collect::<Vec<_>>();    
-    let count = (0..grid.len()).map(|y| {
-            (0..grid[y].len()).map(|x| count_xmas(&grid, (x, y))).sum::<u32>()
-        })
-        .sum::<u32>();
-    println!("{count}");
+    /// Turn by 90° clockwise
+    fn rotate(&self) -> Self {
+        Self::normalized(self.0.iter().map(|&(v, b)| (vec2(-v.y, v.x), b)).collect())
+    }
+
+    /// All distinct rotations of the shape
+    fn rotations(self) -> Vec<Template> {
+        let mut rotations = vec![self];
+        for _ in 0..3 {
+            let next = rotations.last().unwrap().rotate();
+            if !rotations.contains(&next) {
+                rotations.push(next);
+            }
+        }
+        rotations
+    }
 }
 
-fn is_x_mas(grid: &[&[u8]], pos: (usize, usize)) -> bool {
-    if grid[pos.1][pos.0] != b'A' {
-        return false
+impl<'a> Grid<'a> {
+    fn new(input: &'a str) -> Self {
+        let rows: Vec<_> = input.lines().map(|l| l.as_bytes()).collect();
+        let size = Size2D::new(rows[0].len(), rows.len()).to_i32();
+        Grid {
+            rows,
+            bounds: Rect::new(Point2D::origin(), size),
+        }
+    }
+
+    fn get(&self, p: Point2D<i32>) -> Option<u8> {
+        self.bounds
+            .contains(p)
+            .then(|| self.rows[p.y as usize][p.x as usize])
     }
 
-    const DIRS: [Vector2D<i32>; 4] = [vec2(1, -1), vec2(1, 1), vec2(-1, 1), vec2(-1, -1)];
-    let pos = Point2D::from(pos).to_i32();
-    (0..4).any(|d| {
-        let m_pos = [pos + DIRS[d], pos + DIRS[(d + 1) % 4]]; // 2 adjacent positions of M
-        let s_pos = [pos + DIRS[(d + 2) % 4], pos + DIRS[(d + 3) % 4]]; // others S
-        m_pos.iter().all(|p| grid[p.y as usize][p.x as usize] == b'M') &&
-        s_pos.iter().all(|p| grid[p.y as usize][p.x as usize] == b'S')
-    })
+    fn positions(&self) -> impl Iterator<Item = Point2D<i32>> + '_ {
+        (0..self.bounds.height())
+            .flat_map(move |y| (0..self.bounds.width()).map(move |x| Point2D::new(x, y)))
+    }
+
+    /// Occurrences of any of the words in a straight line in all 8 directions
+    fn find_words<'w>(&self, words: &[&'w str]) -> Vec<WordMatch<'w>> {
+        let mut found = Vec::new();
+        for start in self.positions() {
+            for &word in words {
+                for dir in DIRS {
+                    let m = WordMatch { word, start, dir };
+                    if m.cells()
+                        .zip(word.bytes())
+                        .all(|(p, b)| self.get(p) == Some(b))
+                    {
+                        found.push(m);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// Cells of each occurrence of the shape in any rotation
+    fn find_shape(&self, template: Template) -> Vec<Vec<Point2D<i32>>> {
+        let rotations = template.rotations();
+        let mut found = Vec::new();
+        for corner in self.positions() {
+            for t in &rotations {
+                if t.0.iter().all(|&(v, b)| self.get(corner + v) == Some(b)) {
+                    found.push(t.0.iter().map(|&(v, _)| corner + v).collect());
+                }
+            }
+        }
+        found
+    }
+
+    /// Draw the grid, replacing every letter that isn't part of a match with `.`
+    fn highlight(&self, cells: impl IntoIterator<Item = Point2D<i32>>) -> String {
+        let mut keep = vec![vec![false; self.bounds.width() as usize]; self.rows.len()];
+        for p in cells {
+            keep[p.y as usize][p.x as usize] = true;
+        }
+        let mut out = String::new();
+        for (row, keep) in self.rows.iter().zip(keep) {
+            for (&b, k) in row.iter().zip(keep) {
+                out.push(if k { b as char } else { '.' });
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn part1(input: String) {
+    let grid = Grid::new(&input);
+    let found = grid.find_words(&["XMAS"]);
+    if util::verbose() {
+        print!("{}", grid.highlight(found.iter().flat_map(|m| m.cells())));
+    }
+    println!("{}", found.len());
 }
 
 fn part2(input: String) {
-    let grid = input.lines().map(|l| l.as_bytes()).collect::<Vec<_>>();    
-    let count = (1..grid.len() - 1).map(|y| {
-            (1..grid[y].len() - 1).filter(|&x| is_x_mas(&grid, (x, y))).count()
-        })
-        .sum::<usize>();
-    println!("{count}");
+    let grid = Grid::new(&input);
+    let found = grid.find_shape(Template::parse(X_MAS));
+    if util::verbose() {
+        print!("{}", grid.highlight(found.iter().flatten().copied()));
+    }
+    println!("{}", found.len());
 }
 
 util::aoc_main!();
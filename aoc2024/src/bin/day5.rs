@@ -1,111 +1,155 @@
-use std::collections::{HashSet, HashMap, VecDeque};
+use std::collections::{HashMap, HashSet};
 
-fn parse_lists(input: &str) -> Vec<Vec<u32>> {
-    input.lines()
-        .map(|l| l.split(',').map(|e| e.parse().unwrap()).collect())
-        .collect()
+/// Page ordering rules `a|b`, meaning `a` must be printed before `b`
+struct Rules {
+    relation: HashSet<(u32, u32)>,
+    successors: HashMap<u32, Vec<u32>>,
 }
 
-fn parse_relation(input: String) -> (HashSet<(u32, u32)>, Vec<Vec<u32>>) {
-    let (ordering, lists) = input.split_once("\n\n").unwrap();
-    let relation = ordering.lines()
-        .map(|l| {
-            let (a, b) = l.split_once('|').unwrap();
-            (a.parse().unwrap(), b.parse().unwrap())
-        })
-        .collect();
-    (relation, parse_lists(lists))
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Unvisited,
+    Active,
+    Done,
 }
 
-fn parse_graph(input: String) -> (Vec<Vec<u32>>, Vec<Vec<u32>>) {
-    let (ordering, lists) = input.split_once("\n\n").unwrap();
-    let mut graph = Vec::new();
-    for l in ordering.lines() {
-        let (a, b) = l.split_once('|').unwrap();
-        let v: u32 = a.parse().unwrap();
-        let w: u32 = b.parse().unwrap();
-        let new_len = v.max(w) as usize + 1;
-        if new_len > graph.len() {
-            graph.resize(new_len, Vec::new())
+impl Rules {
+    fn parse(input: &str) -> Self {
+        let mut relation = HashSet::new();
+        let mut successors: HashMap<u32, Vec<u32>> = HashMap::new();
+        for l in input.lines() {
+            let (a, b) = l.split_once('|').unwrap();
+            let (a, b) = (a.parse().unwrap(), b.parse().unwrap());
+            relation.insert((a, b));
+            successors.entry(a).or_default().push(b);
+        }
+        Rules {
+            relation,
+            successors,
         }
-        graph[v as usize].push(w);
     }
-    (graph, parse_lists(lists))
-}
 
+    fn pages(&self) -> Vec<u32> {
+        let mut pages: Vec<u32> = self.relation.iter().flat_map(|&(a, b)| [a, b]).collect();
+        pages.sort_unstable();
+        pages.dedup();
+        pages
+    }
 
-fn part1(input: String) {
-    let (relation, lists) = parse_relation(input); 
-    let mut sum = 0;
-    for l in lists {
-        let mut valid = true;
-        for i in 0..l.len() {
-            for j in 0..i {
-                if relation.contains(&(l[i], l[j])) {
-                    valid = false;
-                    break
-                }
+    fn is_ordered(&self, update: &[u32]) -> bool {
+        (0..update.len()).all(|i| (0..i).all(|j| !self.relation.contains(&(update[i], update[j]))))
+    }
+
+    /// Order the pages according to the rules that concern only these pages.
+    /// If those rules contain a cycle, the pages along it are returned instead.
+    fn sort(&self, pages: &[u32]) -> Result<Vec<u32>, Vec<u32>> {
+        let subgraph: HashSet<u32> = pages.iter().copied().collect();
+        let mut marks: HashMap<u32, Mark> = pages.iter().map(|&p| (p, Mark::Unvisited)).collect();
+        let mut order = Vec::with_capacity(pages.len());
+        for &p in pages {
+            if marks[&p] == Mark::Unvisited {
+                let mut path = Vec::new();
+                self.dfs(p, &subgraph, &mut marks, &mut path, &mut order)?;
             }
-            if !valid { break }
-        }
-        if valid {
-            sum += l[l.len() / 2];
         }
+        order.reverse();
+        Ok(order)
     }
-    println!("{sum}");
-}
-
 
-// Topological order of graph, but limited to nodes in the set `subgraph`.
-// Otherwise the graph is not acyclic.
-fn topological_sort(graph: &[Vec<u32>], subgraph: &HashSet<u32>) -> Vec<u32> {
-    let mut order = VecDeque::with_capacity(subgraph.len());
-    let mut marked = vec![false; graph.len()];
-    for &v in subgraph {
-        if !marked[v as usize] {
-            dfs(graph, subgraph, v as usize, &mut marked, &mut order)
+    /// Depth-first search, appending pages to `order` once all their
+    /// successors are done. `path` holds the pages currently being visited,
+    /// so an edge back to one of them closes a cycle.
+    fn dfs(
+        &self,
+        v: u32,
+        subgraph: &HashSet<u32>,
+        marks: &mut HashMap<u32, Mark>,
+        path: &mut Vec<u32>,
+        order: &mut Vec<u32>,
+    ) -> Result<(), Vec<u32>> {
+        marks.insert(v, Mark::Active);
+        path.push(v);
+        let successors = self.successors.get(&v).map_or(&[][..], Vec::as_slice);
+        for &w in successors.iter().filter(|w| subgraph.contains(w)) {
+            match marks[&w] {
+                Mark::Unvisited => self.dfs(w, subgraph, marks, path, order)?,
+                Mark::Active => {
+                    let start = path.iter().position(|&p| p == w).unwrap();
+                    return Err(path[start..].to_vec());
+                }
+                Mark::Done => {}
+            }
         }
+        path.pop();
+        marks.insert(v, Mark::Done);
+        order.push(v);
+        Ok(())
     }
-    order.into()
-}
 
-fn dfs(graph: &[Vec<u32>], subgraph: &HashSet<u32>, v: usize, marked: &mut [bool], order: &mut VecDeque<u32>) {
-    marked[v] = true;
-    for &w in graph[v].iter().filter(|v| subgraph.contains(v)) {
-        if !marked[w as usize] {
-            dfs(graph, subgraph, w as usize, marked, order);
-        }
+    /// A pair of pages without any rule between them
+    fn incomparable(&self, pages: &[u32]) -> Option<(u32, u32)> {
+        pages.iter().enumerate().find_map(|(i, &a)| {
+            pages[..i]
+                .iter()
+                .find(|&&b| !self.relation.contains(&(a, b)) && !self.relation.contains(&(b, a)))
+                .map(|&b| (b, a))
+        })
     }
-    order.push_front(v as u32);
 }
 
-fn rank(order: &[u32]) -> HashMap<u32, u32> {
-    order.iter().enumerate().map(|(i, x)| (*x, i as u32)).collect()
+fn parse(input: &str) -> (Rules, Vec<Vec<u32>>) {
+    let (rules, updates) = input.split_once("\n\n").unwrap();
+    let updates = updates
+        .lines()
+        .map(|l| l.split(',').map(|e| e.parse().unwrap()).collect())
+        .collect();
+    (Rules::parse(rules), updates)
 }
 
-// Part 1 with topological sorting, which is slower
-fn _part1(input: String) {
-    let (graph, lists) = parse_graph(input);
-    let mut sum = 0;
-    for l in lists {
-        let subgraph = HashSet::from_iter(l.iter().copied());
-        let rank = rank(&topological_sort(&graph, &subgraph));
-        if l.is_sorted_by_key(|x| rank[x]) {
-            sum += l[l.len() / 2];
-        }
+fn format_cycle(cycle: &[u32]) -> String {
+    let mut s: Vec<String> = cycle.iter().map(u32::to_string).collect();
+    s.push(cycle[0].to_string());
+    s.join(" -> ")
+}
+
+/// The rules can be used as a comparator for sorting if they contain a rule
+/// for every pair of pages. That holds within each update, even though the
+/// full rule set may not be a total order.
+fn report(rules: &Rules) {
+    let pages = rules.pages();
+    match rules.incomparable(&pages) {
+        None => println!("All pairs of pages are ordered"),
+        Some((a, b)) => println!("No rule between {a} and {b}"),
+    }
+    match rules.sort(&pages) {
+        Ok(_) => println!("Rules are acyclic"),
+        Err(cycle) => println!("Rules contain a cycle: {}", format_cycle(&cycle)),
+    }
+}
+
+fn part1(input: String) {
+    let (rules, updates) = parse(&input);
+    if util::verbose() {
+        report(&rules);
     }
+    let sum: u32 = updates
+        .iter()
+        .filter(|u| rules.is_ordered(u))
+        .map(|u| u[u.len() / 2])
+        .sum();
     println!("{sum}");
 }
 
 fn part2(input: String) {
-    let (graph, lists) = parse_graph(input);
+    let (rules, updates) = parse(&input);
+    if util::verbose() {
+        report(&rules);
+    }
     let mut sum = 0;
-    for mut l in lists {
-        let subgraph = HashSet::from_iter(l.iter().copied());
-        let rank = rank(&topological_sort(&graph, &subgraph));
-        if !l.is_sorted_by_key(|x| rank[x]) {
-            l.sort_unstable_by_key(|x| rank[x]);            
-            sum += l[l.len() / 2];
+    for u in updates.iter().filter(|u| !rules.is_ordered(u)) {
+        match rules.sort(u) {
+            Ok(sorted) => sum += sorted[sorted.len() / 2],
+            Err(cycle) => eprintln!("Can't order update {u:?}, cycle: {}", format_cycle(&cycle)),
         }
     }
     println!("{sum}");
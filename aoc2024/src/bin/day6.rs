@@ -1,5 +1,6 @@
 use euclid::default::{Point2D, Vector2D};
 use euclid::vec2;
+use rayon::prelude::*;
 
 fn parse(input: String) -> (Vec<Vec<bool>>, Point2D<i32>) {
     let mut field = Vec::new();
@@ -52,52 +53,181 @@ fn part1(input: String) {
     println!("{count}")
 }
 
-fn is_loop(field: &[Vec<bool>], start: Point2D<i32>) -> bool {
+const EXIT: u32 = u32::MAX;
+
+/// For every cell and direction, the cell at which the guard stops when walking
+/// from there until the next obstacle, or `EXIT` if the guard leaves the area instead.
+#[derive(Clone)]
+struct JumpTable {
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+    jumps: Vec<u32>,
+}
+
+impl JumpTable {
+    fn new(field: &[Vec<bool>]) -> Self {
+        let width = field[0].len();
+        let height = field.len();
+        let mut table = JumpTable {
+            width,
+            height,
+            blocked: field.concat(),
+            jumps: vec![EXIT; width * height * 4],
+        };
+        for (dir, d) in DIRS.iter().enumerate() {
+            // Visit cells closer to the edge in direction `d` first,
+            // so the jump from the next cell is already known.
+            let xs: Vec<usize> = if d.x > 0 {
+                (0..width).rev().collect()
+            } else {
+                (0..width).collect()
+            };
+            let ys: Vec<usize> = if d.y > 0 {
+                (0..height).rev().collect()
+            } else {
+                (0..height).collect()
+            };
+            for &y in &ys {
+                for &x in &xs {
+                    let pos = Point2D::new(x, y).to_i32();
+                    let here = table.cell(pos);
+                    table.jumps[here * 4 + dir] = match table.next(pos, dir) {
+                        None => EXIT,
+                        Some(next) if table.blocked[table.cell(next)] => here as u32,
+                        Some(next) => table.jumps[table.cell(next) * 4 + dir],
+                    };
+                }
+            }
+        }
+        table
+    }
+
+    fn cell(&self, pos: Point2D<i32>) -> usize {
+        pos.y as usize * self.width + pos.x as usize
+    }
+
+    fn next(&self, pos: Point2D<i32>, dir: usize) -> Option<Point2D<i32>> {
+        let next = pos + DIRS[dir];
+        if next.x < 0 || next.y < 0 || next.x >= self.width as i32 || next.y >= self.height as i32 {
+            None
+        } else {
+            Some(next)
+        }
+    }
+
+    /// Place a new obstacle, returning the overwritten entries for `restore`.
+    fn add_obstacle(&mut self, obstacle: Point2D<i32>) -> Vec<(usize, u32)> {
+        let mut undo = Vec::new();
+        for dir in 0..4 {
+            // Walk backwards from the obstacle. Everything up to the previous
+            // obstacle now stops right in front of the new one.
+            let back = (dir + 2) % 4;
+            let Some(stop) = self.next(obstacle, back) else {
+                continue;
+            };
+            let stop_cell = self.cell(stop) as u32;
+            let mut pos = stop;
+            while !self.blocked[self.cell(pos)] {
+                let idx = self.cell(pos) * 4 + dir;
+                undo.push((idx, self.jumps[idx]));
+                self.jumps[idx] = stop_cell;
+                match self.next(pos, back) {
+                    Some(p) => pos = p,
+                    None => break,
+                }
+            }
+        }
+        undo
+    }
+
+    fn restore(&mut self, undo: Vec<(usize, u32)>) {
+        for (idx, jump) in undo.into_iter().rev() {
+            self.jumps[idx] = jump;
+        }
+    }
+
+    /// Follow the jumps from a cell and direction, marking turns in `seen` with `stamp`.
+    fn is_loop(&self, start: usize, mut dir: usize, seen: &mut [usize], stamp: usize) -> bool {
+        let mut cell = start;
+        loop {
+            let target = self.jumps[cell * 4 + dir];
+            if target == EXIT {
+                break false;
+            }
+            cell = target as usize;
+            dir = (dir + 1) % 4;
+            if seen[cell * 4 + dir] == stamp {
+                break true;
+            }
+            seen[cell * 4 + dir] = stamp;
+        }
+    }
+}
+
+/// All cells on the guard's original path except the start, together with the
+/// position and direction the guard had just before entering them for the first time.
+fn first_entries(
+    field: &[Vec<bool>],
+    start: Point2D<i32>,
+) -> Vec<(Point2D<i32>, Point2D<i32>, usize)> {
     let width = field[0].len();
     let height = field.len();
-    let mut visited = vec![vec![0; width]; height];
-
-    // Start up, then turn right
+    let mut visited = vec![vec![false; width]; height];
+    visited[start.y as usize][start.x as usize] = true;
+    let mut entries = Vec::new();
     let mut dir = 0;
     let mut pos = start;
     loop {
-        // Loop detected
-        if visited[pos.y as usize][pos.x as usize] & (1 << dir) > 0 {
-            break true;
-        }
-        // Record all walked directions at all fields
-        visited[pos.y as usize][pos.x as usize] |= 1 << dir;
         let next = pos + DIRS[dir];
-        // Guard leaves area
         if next.x < 0 || next.y < 0 || next.x >= width as i32 || next.y >= height as i32 {
-            break false;
+            break;
         }
-        // Path blocked
         if field[next.y as usize][next.x as usize] {
-            dir = (dir + 1) % 4 // Turn right, don't move yet
+            dir = (dir + 1) % 4;
         } else {
-            pos = next
+            if !visited[next.y as usize][next.x as usize] {
+                visited[next.y as usize][next.x as usize] = true;
+                entries.push((next, pos, dir));
+            }
+            pos = next;
         }
     }
+    entries
+}
+
+/// Positions where a single new obstacle traps the guard in a loop, sorted by row.
+fn loop_obstructions(field: &[Vec<bool>], start: Point2D<i32>) -> Vec<Point2D<i32>> {
+    let table = JumpTable::new(field);
+    // The path up to the first time the guard reaches the obstacle is unchanged,
+    // so the walk can start right in front of it.
+    let mut obstructions: Vec<_> = first_entries(field, start)
+        .par_iter()
+        .enumerate()
+        .map_init(
+            || (table.clone(), vec![usize::MAX; table.jumps.len()]),
+            |(table, seen), (i, &(obstacle, from, dir))| {
+                let undo = table.add_obstacle(obstacle);
+                let is_loop = table.is_loop(table.cell(from), dir, seen, i);
+                table.restore(undo);
+                is_loop.then_some(obstacle)
+            },
+        )
+        .flatten()
+        .collect();
+    obstructions.sort_unstable_by_key(|p| (p.y, p.x));
+    obstructions
 }
 
 fn part2(input: String) {
-    let (mut field, start) = parse(input);
-    let width = field[0].len();
-    let height = field.len();
-    let normal_visited = visited(&field, start); // Part 1 solution
-    let mut count = 0;
-    for x in 0..width {
-        for y in 0..height {
-            // Only check places that are visited without any obstacles, and don't check start
-            if normal_visited[y][x] && !(x as i32 == start.x && y as i32 == start.y) {
-                field[y][x] = true; // Set obstacle
-                count += is_loop(&field, start) as u32;
-                field[y][x] = false; // Remove obstacle
-            }
+    let (field, start) = parse(input);
+    let obstructions = loop_obstructions(&field, start);
+    if util::verbose() {
+        for p in &obstructions {
+            println!("{},{}", p.x, p.y);
         }
     }
-    println!("{count}");
+    println!("{}", obstructions.len());
 }
 
 util::aoc_main!();
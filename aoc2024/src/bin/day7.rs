@@ -1,44 +1,110 @@
+use rayon::prelude::*;
+
+#[derive(Clone, Copy)]
+enum Op {
+    Add,
+    Mul,
+    Concat,
+}
+
+const PART1_OPS: &[Op] = &[Op::Add, Op::Mul];
+const PART2_OPS: &[Op] = &[Op::Add, Op::Mul, Op::Concat];
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Mul => "*",
+            Op::Concat => "||",
+        }
+    }
+
+    /// Find the left operand `a` with `a <op> n == result`. All values are positive.
+    fn invert(self, result: u64, n: u64) -> Option<u64> {
+        match self {
+            Op::Add => result.checked_sub(n),
+            Op::Mul => (n != 0 && result.is_multiple_of(n)).then(|| result / n),
+            Op::Concat => {
+                let shift = 10u64.pow(n.checked_ilog10().unwrap_or(0) + 1);
+                (result % shift == n).then_some(result / shift)
+            }
+        }
+    }
+}
+
 fn parse_line(l: &str) -> (u64, Vec<u64>) {
     let (first, last) = l.split_once(": ").unwrap();
     let solution = first.parse().unwrap();
-    let values = last.split_whitespace().map(|s| s.parse().unwrap()).collect();
+    let values = last
+        .split_whitespace()
+        .map(|s| s.parse().unwrap())
+        .collect();
     (solution, values)
 }
 
-fn sum_valid(input: &str, valid: fn(u64, u64, &[u64]) -> bool) -> u64 {
-    let mut sum = 0;
-    for l in input.lines() {
-        let (solution, values) = parse_line(l);
-        if valid(solution, values[0], &values[1..]) {
-            sum += solution;
+/// Search operators from right to left by undoing the last operation on the
+/// result. Most branches end early because the result can't be divided or
+/// doesn't end with the right digits. Found operators are pushed to `found`
+/// in reverse order.
+fn search(result: u64, values: &[u64], ops: &[Op], found: &mut Vec<Op>) -> bool {
+    let (&last, rest) = values.split_last().unwrap();
+    if rest.is_empty() {
+        return result == last;
+    }
+    for &op in ops {
+        if let Some(prev) = op.invert(result, last) {
+            found.push(op);
+            if search(prev, rest, ops, found) {
+                return true;
+            }
+            found.pop();
         }
     }
-    sum
+    false
 }
 
-fn part1(input: String) {
-    fn is_valid(solution: u64, acc: u64, values: &[u64]) -> bool {
-        match values.first() {
-            None => acc == solution,
-            Some(n) => is_valid(solution, acc + n, &values[1..]) ||
-                is_valid(solution, acc * n, &values[1..]),
-        }
+/// Operators between each pair of values that produce `solution`, if there are any.
+fn solve(solution: u64, values: &[u64], ops: &[Op]) -> Option<Vec<Op>> {
+    let mut found = Vec::with_capacity(values.len() - 1);
+    if search(solution, values, ops, &mut found) {
+        found.reverse();
+        Some(found)
+    } else {
+        None
     }
+}
 
-    println!("{}", sum_valid(&input, is_valid));
+fn equation(solution: u64, values: &[u64], ops: &[Op]) -> String {
+    let mut s = format!("{solution} = {}", values[0]);
+    for (op, n) in ops.iter().zip(&values[1..]) {
+        s += &format!(" {} {n}", op.symbol());
+    }
+    s
 }
 
-fn part2(input: String) {
-    fn is_valid(solution: u64, acc: u64, values: &[u64]) -> bool {
-        match values.first() {
-            None => acc == solution,
-            Some(n) => is_valid(solution, acc + n, &values[1..]) ||
-                is_valid(solution, acc * n, &values[1..]) ||
-                is_valid(solution, acc * 10u64.pow(n.ilog10() + 1) + n, &values[1..]), 
+fn sum_valid(input: &str, ops: &[Op]) -> u64 {
+    let solved: Vec<_> = input
+        .par_lines()
+        .filter_map(|l| {
+            let (solution, values) = parse_line(l);
+            let found = solve(solution, &values, ops)?;
+            Some((solution, values, found))
+        })
+        .collect();
+    if util::verbose() {
+        for (solution, values, found) in &solved {
+            println!("{}", equation(*solution, values, found));
         }
     }
+    solved.iter().map(|(solution, ..)| solution).sum()
+}
 
-    println!("{}", sum_valid(&input, is_valid));
+fn part1(input: String) {
+    println!("{}", sum_valid(&input, PART1_OPS));
+}
+
+fn part2(input: String) {
+    println!("{}", sum_valid(&input, PART2_OPS));
 }
 
 util::aoc_main!();
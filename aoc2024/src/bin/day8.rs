@@ -1,11 +1,18 @@
 use euclid::default::*;
 
-const N_ANTENNAS: usize = (b'z' - b'0') as usize + 1;
-// For each frequency (from b'0' to b'z') the list of antenna positions
-type Antennas = Box<[Vec<Point2D<i32>>]>;
+/// For each frequency byte the list of antenna positions
+type Antennas = Vec<Vec<Point2D<i32>>>;
 
-fn parse(input: String) -> (Antennas, Rect<i32>) {
-    let mut antennas = vec![Vec::new(); N_ANTENNAS].into_boxed_slice();
+/// Which multiples of the vector between two antennas produce antinodes
+enum Harmonics<'a> {
+    /// Only the given multiples, measured from either antenna
+    Multiples(&'a [i32]),
+    /// Every multiple, covering the whole line through both antennas
+    All,
+}
+
+fn parse(input: &str) -> (Antennas, Rect<i32>) {
+    let mut antennas = vec![Vec::new(); 256];
     let mut width = 0;
     let mut height = 0;
     for (y, l) in input.lines().enumerate() {
@@ -16,64 +23,97 @@ fn parse(input: String) -> (Antennas, Rect<i32>) {
             assert!(width == l.len())
         }
         for (x, b) in l.bytes().enumerate().filter(|(_, b)| *b != b'.') {
-            antennas[(b - b'0') as usize].push(Point2D::new(x, y).to_i32())
+            antennas[b as usize].push(Point2D::new(x, y).to_i32())
         }
     }
     let bounds = Rect::new(Point2D::origin(), Size2D::new(width, height).to_i32());
     (antennas, bounds)
 }
 
-fn part1(input: String) {
-    let (antennas, bounds) = parse(input);
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Mark antinodes of all pairs of antennas with the same frequency. With
+/// `reduce`, the vector between two antennas is divided by the gcd of its
+/// coordinates, so that all lattice points in between are hit as well.
+fn antinodes(
+    antennas: &Antennas,
+    bounds: Rect<i32>,
+    harmonics: &Harmonics,
+    reduce: bool,
+) -> Vec<Vec<bool>> {
     let mut antinodes = vec![vec![false; bounds.width() as usize]; bounds.height() as usize];
+    let mut mark = |p: Point2D<i32>| {
+        let inside = bounds.contains(p);
+        if inside {
+            antinodes[p.y as usize][p.x as usize] = true;
+        }
+        inside
+    };
     for list in antennas.iter().filter(|l| !l.is_empty()) {
         for (i, &a) in list.iter().enumerate().skip(1) {
             for &b in list.iter().take(i) {
-                let diff = b - a;
-                let ax = a - diff;
-                if bounds.contains(ax) {
-                    antinodes[ax.y as usize][ax.x as usize] = true;
+                let mut diff = b - a;
+                if reduce {
+                    diff /= gcd(diff.x, diff.y);
                 }
-                let bx = b + diff;
-                if bounds.contains(bx) {
-                    antinodes[bx.y as usize][bx.x as usize] = true;
+                match harmonics {
+                    Harmonics::Multiples(ks) => {
+                        for &k in *ks {
+                            mark(a + diff * k);
+                            mark(b - diff * k);
+                        }
+                    }
+                    Harmonics::All => {
+                        // Start at antenna a, keep going until hitting bounds
+                        let mut ax = a;
+                        while mark(ax) {
+                            ax -= diff;
+                        }
+                        let mut bx = a + diff;
+                        while mark(bx) {
+                            bx += diff;
+                        }
+                    }
                 }
             }
         }
     }
-    let sum = antinodes
-        .iter()
-        .map(|row| row.iter().map(|b| u32::from(*b)).sum::<u32>())
-        .sum::<u32>();
-    println!("{sum}");
+    antinodes
 }
 
-fn part2(input: String) {
-    let (antennas, bounds) = parse(input);
-    let mut antinodes = vec![vec![false; bounds.width() as usize]; bounds.height() as usize];
-    for list in antennas.iter().filter(|l| !l.is_empty()) {
-        for (i, &a) in list.iter().enumerate().skip(1) {
-            for &b in list.iter().take(i) {
-                let diff = b - a;
-                // Start at antenna a, keep going until hitting bounds
-                let mut ax = a;
-                while bounds.contains(ax) {
-                    antinodes[ax.y as usize][ax.x as usize] = true;
-                    ax -= diff;
-                }
-                let mut bx = b;
-                while bounds.contains(bx) {
-                    antinodes[bx.y as usize][bx.x as usize] = true;
-                    bx += diff;
-                }
-            }
+/// Draw the map with all antinodes that aren't covered by an antenna as `#`.
+fn render(input: &str, antinodes: &[Vec<bool>]) -> String {
+    let mut out = String::new();
+    for (l, row) in input.lines().zip(antinodes) {
+        for (c, &a) in l.chars().zip(row) {
+            out.push(if a && c == '.' { '#' } else { c });
         }
+        out.push('\n');
+    }
+    out
+}
+
+fn solve(input: &str, harmonics: Harmonics, reduce: bool) -> usize {
+    let (antennas, bounds) = parse(input);
+    let antinodes = antinodes(&antennas, bounds, &harmonics, reduce);
+    if util::verbose() {
+        print!("{}", render(input, &antinodes));
     }
-    let sum = antinodes
-        .iter()
-        .map(|row| row.iter().map(|b| u32::from(*b)).sum::<u32>())
-        .sum::<u32>();
-    println!("{sum}");
+    antinodes.iter().flatten().filter(|&&a| a).count()
+}
+
+fn part1(input: String) {
+    println!("{}", solve(&input, Harmonics::Multiples(&[2]), false));
+}
+
+fn part2(input: String) {
+    println!("{}", solve(&input, Harmonics::All, true));
 }
 
 util::aoc_main!();
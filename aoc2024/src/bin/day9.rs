@@ -1,93 +1,200 @@
-fn part1(input: String) {
-    let mut id: u64 = 0;
-    let mut disk = Vec::new();
-    let mut file = true;
-    for b in input.trim().bytes() {
-        let num: usize = (b - b'0') as usize;
-        if file {
-            disk.extend(vec![Some(id); num]);
-            id += 1;
-        } else {
-            disk.extend(vec![None; num]);
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt;
+
+/// Longest disk that is still drawn in verbose mode
+const MAX_PRINT_LEN: usize = 200;
+
+#[derive(Clone, Copy)]
+enum Strategy {
+    /// Move single blocks from the end into the leftmost free block
+    Blocks,
+    /// Move whole files into the leftmost span that fits
+    FirstFit,
+    /// Move whole files into the smallest span that fits, leftmost among equals
+    BestFit,
+}
+
+/// Contiguous run of blocks belonging to one file
+#[derive(Clone, Copy)]
+struct Extent {
+    pos: usize,
+    len: usize,
+    id: u64,
+}
+
+#[derive(Clone)]
+struct DiskMap {
+    len: usize,
+    files: Vec<Extent>,
+    /// Tuples of (pos, len)
+    free: Vec<(usize, usize)>,
+}
+
+impl DiskMap {
+    fn parse(input: &str) -> Self {
+        let mut files = Vec::new();
+        let mut free = Vec::new();
+        let mut pos = 0;
+        for (i, b) in input.trim().bytes().enumerate() {
+            let len = (b - b'0') as usize;
+            if i % 2 == 0 {
+                files.push(Extent {
+                    pos,
+                    len,
+                    id: i as u64 / 2,
+                });
+            } else if len > 0 {
+                free.push((pos, len));
+            }
+            pos += len;
+        }
+        DiskMap {
+            len: pos,
+            files,
+            free,
         }
-        file = !file;
     }
 
-    let mut first_free = 0;
-    while disk[first_free].is_some() {
-        first_free += 1
+    fn blocks(&self) -> Vec<Option<u64>> {
+        let mut blocks = vec![None; self.len];
+        for f in &self.files {
+            blocks[f.pos..f.pos + f.len].fill(Some(f.id));
+        }
+        blocks
     }
 
-    let mut last_file = disk.len() - 1;
-    while disk[last_file].is_none() {
-        last_file -= 1
+    fn compact(&mut self, strategy: Strategy) {
+        match strategy {
+            Strategy::Blocks => self.compact_blocks(),
+            Strategy::FirstFit => self.compact_files(false),
+            Strategy::BestFit => self.compact_files(true),
+        }
     }
 
-    while first_free < last_file {
-        disk[first_free] = disk[last_file];
-        disk[last_file] = None;
-        while disk[first_free].is_some() {
-            first_free += 1
+    fn compact_blocks(&mut self) {
+        let mut disk = self.blocks();
+        let mut first_free = 0;
+        let mut last_file = disk.len();
+        loop {
+            while first_free < disk.len() && disk[first_free].is_some() {
+                first_free += 1
+            }
+            while last_file > 0 && disk[last_file - 1].is_none() {
+                last_file -= 1
+            }
+            if first_free >= last_file {
+                break;
+            }
+            disk[first_free] = disk[last_file - 1].take();
         }
-        while disk[last_file].is_none() {
-            last_file -= 1
+
+        // Collect runs of equal IDs back into extents
+        self.files.clear();
+        for (pos, block) in disk.iter().enumerate() {
+            let Some(id) = *block else { continue };
+            match self.files.last_mut() {
+                Some(e) if e.id == id && e.pos + e.len == pos => e.len += 1,
+                _ => self.files.push(Extent { pos, len: 1, id }),
+            }
         }
+        self.update_free();
     }
 
-    let checksum = disk
-        .iter()
-        .filter_map(|e| *e)
-        .enumerate()
-        .map(|(i, id)| i as u64 * id)
-        .sum::<u64>();
-    println!("{checksum}");
-}
+    /// Move each file once, in order of decreasing ID. Free spans are kept in
+    /// one min-heap of positions per span size, so the leftmost span of each
+    /// size is found quickly.
+    fn compact_files(&mut self, best_fit: bool) {
+        let mut spans: BTreeMap<usize, BinaryHeap<Reverse<usize>>> = BTreeMap::new();
+        for &(pos, len) in &self.free {
+            spans.entry(len).or_default().push(Reverse(pos));
+        }
+        for file in self.files.iter_mut().rev() {
+            let fitting = spans.range(file.len..).filter_map(|(&size, heap)| {
+                let &Reverse(pos) = heap.peek()?;
+                // Only move files to the left
+                (pos < file.pos).then_some((size, pos))
+            });
+            let target = if best_fit {
+                fitting.min()
+            } else {
+                fitting.min_by_key(|&(_, pos)| pos)
+            };
+            let Some((size, pos)) = target else { continue };
+            spans.get_mut(&size).unwrap().pop();
+            let rest = size - file.len;
+            if rest > 0 {
+                spans.entry(rest).or_default().push(Reverse(pos + file.len));
+            }
+            // The vacated space is never used again, because all files
+            // still to be moved lie further left.
+            file.pos = pos;
+        }
+        self.files.sort_unstable_by_key(|e| e.pos);
+        self.update_free();
+    }
 
-fn part2(input: String) {
-    // Tuples of (idx, size)
-    let mut free_spaces = Vec::new();
-    // Tuples of (idx, size, id)
-    let mut files = Vec::new();
-
-    let mut id: u64 = 0;
-    let mut disk_len = 0;
-    let mut file = true;
-    for b in input.trim().bytes() {
-        let num = (b - b'0') as u64;
-        if file {
-            files.push((disk_len, num, id));
-            id += 1;
-        } else {
-            free_spaces.push((disk_len, num));
+    /// Recompute the free spans as the gaps between the files, which must be
+    /// sorted by position.
+    fn update_free(&mut self) {
+        self.free.clear();
+        let mut pos = 0;
+        for f in self.files.iter().filter(|f| f.len > 0) {
+            if f.pos > pos {
+                self.free.push((pos, f.pos - pos));
+            }
+            pos = pos.max(f.pos + f.len);
+        }
+        if pos < self.len {
+            self.free.push((pos, self.len - pos));
         }
-        disk_len += num;
-        file = !file;
     }
 
-    for (idx, size, _id) in files.iter_mut().rev() {
-        match free_spaces
-            .iter_mut()
-            // Only search spaces left of file
-            .take_while(|(sp_idx, _space)| sp_idx < idx)
-            .find(|(_sp_idx, space)| space >= size)
-        {
-            None => {} // No space found
-            Some((sp_idx, space)) => {
-                // Move file into space
-                *idx = *sp_idx;
-                // Reduce free space
-                *sp_idx += *size;
-                *space -= *size;
+    fn checksum(&self) -> u64 {
+        let sum_range = |n: usize| if n == 0 { 0 } else { (n * (n - 1)) / 2 };
+        self.files
+            .iter()
+            .map(|e| (sum_range(e.pos + e.len) - sum_range(e.pos)) as u64 * e.id)
+            .sum()
+    }
+}
+
+/// Draws the disk like `00...111`. Only the last digit of each ID is shown.
+impl fmt::Display for DiskMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for block in self.blocks() {
+            match block {
+                Some(id) => write!(f, "{}", id % 10)?,
+                None => write!(f, ".")?,
             }
         }
+        Ok(())
     }
+}
 
-    let sum_range = |n| if n == 0 { 0 } else { (n * (n - 1)) / 2 };
-    let checksum = files
-        .iter()
-        .map(|(idx, size, id)| (sum_range(idx + size) - sum_range(*idx)) * id)
-        .sum::<u64>();
-    println!("{checksum}");
+fn solve(input: &str, strategy: Strategy) -> DiskMap {
+    let mut disk = DiskMap::parse(input);
+    let verbose = util::verbose() && disk.len <= MAX_PRINT_LEN;
+    if verbose {
+        println!("{disk}");
+    }
+    disk.compact(strategy);
+    if verbose {
+        println!("{disk}");
+    }
+    disk
+}
+
+fn part1(input: String) {
+    println!("{}", solve(&input, Strategy::Blocks).checksum());
+}
+
+fn part2(input: String) {
+    println!("{}", solve(&input, Strategy::FirstFit).checksum());
+    if util::verbose() {
+        let best_fit = solve(&input, Strategy::BestFit);
+        println!("Best fit: {}", best_fit.checksum());
+    }
 }
 
 util::aoc_main!();
@@ -1,73 +1,141 @@
 use std::collections::HashMap;
 
-// Preassigned indices for special nodes
-const YOU: u32 = 0;
-const OUT: u32 = 1;
-const SVR: u32 = 2;
-const DAC: u32 = 3;
-const FFT: u32 = 4;
+const WAYPOINTS: &[&str] = &["dac", "fft"];
 
-fn parse_input(input: &str) -> Vec<Vec<u32>> {
-    let mut names: HashMap<&str, u32> = HashMap::default();
-    // Hardcode indices of special nodes
-    names.insert("you", YOU);
-    names.insert("out", OUT);
-    names.insert("svr", SVR);
-    names.insert("dac", DAC);
-    names.insert("fft", FFT);
-    let mut cur_num: u32 = FFT;
-    let mut out = Vec::new();
-    for l in input.lines() {
-        let (vs, adjs) = l.split_once(": ").unwrap();
-        let v = *names.entry(vs).or_insert_with(|| {
-            cur_num += 1;
-            cur_num
-        });
-        let mut node = Vec::new();
-        let mut max_node = v;
-        for adj in adjs.split_ascii_whitespace() {
-            let u = *names.entry(adj).or_insert_with(|| {
-                cur_num += 1;
-                cur_num
-            });
-            node.push(u);
-            max_node = max_node.max(u);
+struct Graph<'a> {
+    ids: HashMap<&'a str, usize>,
+    names: Vec<&'a str>,
+    adj: Vec<Vec<usize>>,
+}
+
+impl<'a> Graph<'a> {
+    fn parse(input: &'a str) -> Self {
+        let mut graph = Graph {
+            ids: HashMap::new(),
+            names: Vec::new(),
+            adj: Vec::new(),
+        };
+        for l in input.lines() {
+            let (v, adjs) = l.split_once(": ").unwrap();
+            let v = graph.id(v);
+            for u in adjs.split_ascii_whitespace() {
+                let u = graph.id(u);
+                graph.adj[v].push(u);
+            }
         }
-        if max_node as usize >= out.len() {
-            out.resize(max_node as usize + 1, Vec::new());
+        graph
+    }
+
+    fn id(&mut self, name: &'a str) -> usize {
+        *self.ids.entry(name).or_insert_with(|| {
+            self.names.push(name);
+            self.adj.push(Vec::new());
+            self.names.len() - 1
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Result<usize, String> {
+        self.ids
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("Unknown node {name}"))
+    }
+
+    /// Number of paths from `start` to `end` that visit all `required` nodes
+    /// and none of the `forbidden` ones
+    fn count_paths(
+        &self,
+        start: &str,
+        end: &str,
+        required: &[&str],
+        forbidden: &[&str],
+    ) -> Result<u64, String> {
+        if required.len() > 16 {
+            return Err(format!("Too many waypoints: {}", required.len()));
+        }
+        let mut counter = PathCounter {
+            graph: self,
+            end: self.lookup(end)?,
+            waypoint_bit: vec![0; self.names.len()],
+            forbidden: vec![false; self.names.len()],
+            all: (1 << required.len()) - 1,
+            visits: HashMap::new(),
+        };
+        for (i, w) in required.iter().enumerate() {
+            counter.waypoint_bit[self.lookup(w)?] = 1 << i;
         }
-        out[v as usize] = node;
+        for f in forbidden {
+            counter.forbidden[self.lookup(f)?] = true;
+        }
+        counter.dfs(self.lookup(start)?, 0)
     }
-    out
 }
 
-type Cache = HashMap<(u32, bool, bool), u64>;
+enum Visit {
+    InProgress,
+    Done(u64),
+}
 
-fn dfs(v: u32, graph: &[Vec<u32>], has_dac: bool, has_fft: bool, cache: &mut Cache) -> u64 {
-    if v == OUT && has_dac && has_fft {
-        return 1;
+struct PathCounter<'a> {
+    graph: &'a Graph<'a>,
+    end: usize,
+    /// Bit of each required node in the mask of visited waypoints
+    waypoint_bit: Vec<u32>,
+    forbidden: Vec<bool>,
+    all: u32,
+    visits: HashMap<(usize, u32), Visit>,
+}
+
+impl PathCounter<'_> {
+    /// Paths to the end from `v`, with the waypoints in `mask` visited before
+    fn dfs(&mut self, v: usize, mask: u32) -> Result<u64, String> {
+        if self.forbidden[v] {
+            return Ok(0);
+        }
+        let mask = mask | self.waypoint_bit[v];
+        if v == self.end {
+            return Ok(u64::from(mask == self.all));
+        }
+        match self.visits.get(&(v, mask)) {
+            Some(Visit::Done(n)) => return Ok(*n),
+            // The mask can't shrink along a path, so coming back to the same
+            // state means the path went around a cycle
+            Some(Visit::InProgress) => {
+                return Err(format!("Cycle through {}", self.graph.names[v]));
+            }
+            None => {}
+        }
+        self.visits.insert((v, mask), Visit::InProgress);
+        let mut n = 0;
+        for &u in &self.graph.adj[v] {
+            n += self.dfs(u, mask)?;
+        }
+        self.visits.insert((v, mask), Visit::Done(n));
+        Ok(n)
     }
-    if let Some(res) = cache.get(&(v, has_dac, has_fft)) {
-        return *res;
+}
+
+fn print_result(result: Result<u64, String>) {
+    match result {
+        Ok(n) => println!("{n}"),
+        Err(e) => eprintln!("{e}"),
     }
-    let new_dac = has_dac || v == DAC;
-    let new_fft = has_fft || v == FFT;
-    let num: u64 = graph[v as usize]
-        .iter()
-        .map(|&adj| dfs(adj, graph, new_dac, new_fft, cache))
-        .sum();
-    cache.insert((v, has_dac, has_fft), num);
-    num
 }
 
 fn part1(input: String) {
-    let graph = parse_input(&input);
-    println!("{}", dfs(YOU, &graph, true, true, &mut HashMap::default()));
+    let graph = Graph::parse(&input);
+    print_result(graph.count_paths("you", "out", &[], &[]));
 }
 
 fn part2(input: String) {
-    let graph = parse_input(&input);
-    println!("{}", dfs(SVR, &graph, false, false, &mut HashMap::default()));
+    let graph = Graph::parse(&input);
+    if util::verbose() {
+        let avoiding = graph.count_paths("svr", "out", &[], WAYPOINTS);
+        if let Ok(n) = avoiding {
+            println!("Paths avoiding {}: {n}", WAYPOINTS.join(" and "));
+        }
+    }
+    print_result(graph.count_paths("svr", "out", WAYPOINTS, &[]));
 }
 
 util::aoc_main!();
@@ -1,37 +1,282 @@
-type Tree = ((u32, u32), Vec<u32>);
+use std::time::{Duration, Instant};
 
-fn parse_input(input: &str) -> (Vec<u32>, Vec<Tree>) {
-    let parts: Vec<&str> = input.split("\n\n").collect();
-    let mut shapes = Vec::with_capacity(parts.len() - 1);
-    for p in parts.iter().take(parts.len() - 1) {
-        let n_filled = p.chars().filter(|c| *c == '#').count();
-        shapes.push(n_filled as u32);
+/// Give up on a region after searching this long
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Cells of a shape relative to its first cell in reading order
+type Orientation = Vec<(i32, i32)>;
+
+struct Shape {
+    area: usize,
+    /// Width and height of the bounding box
+    size: (usize, usize),
+    /// All distinct rotations and reflections
+    orientations: Vec<Orientation>,
+}
+
+impl Shape {
+    fn parse(s: &str) -> Self {
+        let cells: Vec<(i32, i32)> = s
+            .lines()
+            .skip(1) // Index line
+            .enumerate()
+            .flat_map(|(y, l)| {
+                l.bytes()
+                    .enumerate()
+                    .filter(|&(_, b)| b == b'#')
+                    .map(move |(x, _)| (x as i32, y as i32))
+            })
+            .collect();
+        let width = cells.iter().map(|c| c.0).max().unwrap() + 1;
+        let height = cells.iter().map(|c| c.1).max().unwrap() + 1;
+
+        let mut orientations: Vec<Orientation> = Vec::with_capacity(8);
+        let mut cur = cells.clone();
+        for i in 0..8 {
+            if i == 4 {
+                // Mirror, then go through all rotations again
+                cur = cells.iter().map(|&(x, y)| (-x, y)).collect();
+            }
+            let norm = normalize(&cur);
+            if !orientations.contains(&norm) {
+                orientations.push(norm);
+            }
+            cur = cur.iter().map(|&(x, y)| (-y, x)).collect();
+        }
+        Shape {
+            area: cells.len(),
+            size: (width as usize, height as usize),
+            orientations,
+        }
     }
+}
+
+/// Sort cells in reading order and make them relative to the first one.
+fn normalize(cells: &[(i32, i32)]) -> Orientation {
+    let mut sorted = cells.to_vec();
+    sorted.sort_unstable_by_key(|&(x, y)| (y, x));
+    let (x0, y0) = sorted[0];
+    sorted.iter().map(|&(x, y)| (x - x0, y - y0)).collect()
+}
+
+struct Region {
+    width: usize,
+    height: usize,
+    counts: Vec<usize>,
+}
 
-    let mut trees = Vec::new();
+enum Packing {
+    /// Index of the piece covering each cell, in rows
+    Fits(Vec<Vec<Option<usize>>>),
+    Impossible,
+    Timeout,
+}
+
+fn parse_input(input: &str) -> (Vec<Shape>, Vec<Region>) {
+    let parts: Vec<&str> = input.split("\n\n").collect();
+    let shapes = parts[..parts.len() - 1]
+        .iter()
+        .map(|p| Shape::parse(p))
+        .collect();
+
+    let mut regions = Vec::new();
     for l in parts.last().unwrap().lines() {
         let (size_s, counts_s) = l.split_once(": ").unwrap();
         let (x_s, y_s) = size_s.split_once('x').unwrap();
-        let size = (x_s.parse::<u32>().unwrap(), y_s.parse::<u32>().unwrap());
         let counts = counts_s
             .split_ascii_whitespace()
-            .map(|s| s.parse::<u32>().unwrap())
+            .map(|s| s.parse().unwrap())
             .collect();
-        trees.push((size, counts));
+        regions.push(Region {
+            width: x_s.parse().unwrap(),
+            height: y_s.parse().unwrap(),
+            counts,
+        });
     }
-    (shapes, trees)
+    (shapes, regions)
 }
 
-fn part1(input: String) {
-    let (shapes, trees) = parse_input(&input);
-    let n = trees
-        .iter()
-        .filter(|(size, counts)| {
-            let size_needed: u32 = counts.iter().zip(&shapes).map(|(c, s)| c * s).sum();
-            let size_available = size.0 * size.1;
-            size_needed <= size_available
+struct Packer<'a> {
+    shapes: &'a [Shape],
+    width: usize,
+    height: usize,
+    /// Piece index per cell. Cells that are left empty on purpose are `Some(usize::MAX)`.
+    grid: Vec<Option<usize>>,
+    counts: Vec<usize>,
+    n_placed: usize,
+    start: Instant,
+    nodes: u64,
+    timed_out: bool,
+}
+
+impl Packer<'_> {
+    fn fits(&self, orientation: &Orientation, pos: usize) -> bool {
+        let (x, y) = ((pos % self.width) as i32, (pos / self.width) as i32);
+        orientation.iter().all(|&(dx, dy)| {
+            let (cx, cy) = (x + dx, y + dy);
+            cx >= 0
+                && cy >= 0
+                && (cx as usize) < self.width
+                && (cy as usize) < self.height
+                && self.grid[cy as usize * self.width + cx as usize].is_none()
         })
-        .count();
+    }
+
+    fn set(&mut self, orientation: &Orientation, pos: usize, value: Option<usize>) {
+        let (x, y) = ((pos % self.width) as i32, (pos / self.width) as i32);
+        for &(dx, dy) in orientation {
+            self.grid[(y + dy) as usize * self.width + (x + dx) as usize] = value;
+        }
+    }
+
+    /// Fill the first free cell with any piece in any orientation, or leave
+    /// it empty as long as enough free cells remain for all other pieces.
+    fn search(&mut self, pos: usize, slack: usize) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1 << 12) && self.start.elapsed() > TIMEOUT {
+            self.timed_out = true;
+        }
+        if self.timed_out {
+            return false;
+        }
+        if self.counts.iter().all(|&c| c == 0) {
+            return true;
+        }
+        let Some(pos) = (pos..self.grid.len()).find(|&p| self.grid[p].is_none()) else {
+            return false;
+        };
+        for s in 0..self.shapes.len() {
+            if self.counts[s] == 0 {
+                continue;
+            }
+            for o in 0..self.shapes[s].orientations.len() {
+                let orientation = &self.shapes[s].orientations[o];
+                if !self.fits(orientation, pos) {
+                    continue;
+                }
+                let orientation = orientation.clone();
+                self.set(&orientation, pos, Some(self.n_placed));
+                self.counts[s] -= 1;
+                self.n_placed += 1;
+                if self.search(pos + 1, slack) {
+                    return true;
+                }
+                self.n_placed -= 1;
+                self.counts[s] += 1;
+                self.set(&orientation, pos, None);
+            }
+        }
+        if slack > 0 {
+            self.grid[pos] = Some(usize::MAX);
+            if self.search(pos + 1, slack - 1) {
+                return true;
+            }
+            self.grid[pos] = None;
+        }
+        false
+    }
+}
+
+/// Place pieces side by side in a grid of their common bounding box. Only
+/// possible if that grid is large enough to give every piece its own box.
+fn pack_in_boxes(shapes: &[Shape], region: &Region) -> Option<Vec<Vec<Option<usize>>>> {
+    let box_w = shapes.iter().map(|s| s.size.0).max()?;
+    let box_h = shapes.iter().map(|s| s.size.1).max()?;
+    let per_row = region.width / box_w;
+    let n_pieces: usize = region.counts.iter().sum();
+    if per_row * (region.height / box_h) < n_pieces {
+        return None;
+    }
+    let mut grid = vec![vec![None; region.width]; region.height];
+    let pieces = region
+        .counts
+        .iter()
+        .enumerate()
+        .flat_map(|(s, &c)| std::iter::repeat_n(s, c));
+    for (i, s) in pieces.enumerate() {
+        let (bx, by) = ((i % per_row) * box_w, (i / per_row) * box_h);
+        // The first cell isn't necessarily in the first column
+        let min_dx = shapes[s].orientations[0].iter().map(|c| c.0).min().unwrap();
+        for &(dx, dy) in &shapes[s].orientations[0] {
+            let x = bx as i32 + dx - min_dx;
+            grid[by + dy as usize][x as usize] = Some(i);
+        }
+    }
+    Some(grid)
+}
+
+fn pack(shapes: &[Shape], region: &Region) -> Packing {
+    let needed: usize = region
+        .counts
+        .iter()
+        .zip(shapes)
+        .map(|(c, s)| c * s.area)
+        .sum();
+    let available = region.width * region.height;
+    if needed > available {
+        return Packing::Impossible;
+    }
+    if let Some(grid) = pack_in_boxes(shapes, region) {
+        return Packing::Fits(grid);
+    }
+    let mut packer = Packer {
+        shapes,
+        width: region.width,
+        height: region.height,
+        grid: vec![None; available],
+        counts: region.counts.clone(),
+        n_placed: 0,
+        start: Instant::now(),
+        nodes: 0,
+        timed_out: false,
+    };
+    if packer.search(0, available - needed) {
+        let rows = packer
+            .grid
+            .chunks(region.width)
+            .map(|row| row.iter().map(|c| c.filter(|&i| i != usize::MAX)).collect())
+            .collect();
+        Packing::Fits(rows)
+    } else if packer.timed_out {
+        Packing::Timeout
+    } else {
+        Packing::Impossible
+    }
+}
+
+/// Draw each piece with its own letter, repeating after Z.
+fn draw(grid: &[Vec<Option<usize>>]) -> String {
+    let mut out = String::new();
+    for row in grid {
+        for cell in row {
+            out.push(match cell {
+                Some(i) => (b'A' + (i % 26) as u8) as char,
+                None => '.',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn part1(input: String) {
+    let (shapes, regions) = parse_input(&input);
+    let verbose = util::verbose();
+    let mut n = 0;
+    for region in &regions {
+        let size = format!("{}x{}", region.width, region.height);
+        match pack(&shapes, region) {
+            Packing::Fits(grid) => {
+                n += 1;
+                if verbose {
+                    println!("{size}: Fits\n{}", draw(&grid));
+                }
+            }
+            Packing::Impossible if verbose => println!("{size}: Impossible"),
+            Packing::Impossible => {}
+            Packing::Timeout => eprintln!("{size}: Timed out"),
+        }
+    }
     println!("{n}");
 }
 
@@ -1,4 +1,7 @@
-use std::ops::RangeInclusive;
+use std::ops::{AddAssign, RangeInclusive, SubAssign};
+
+/// Invalid IDs of a range are only listed if there are at most this many
+const MAX_PRINT: u128 = 20;
 
 fn parse_input(input: &str) -> Vec<RangeInclusive<u64>> {
     input
@@ -11,48 +14,151 @@ fn parse_input(input: &str) -> Vec<RangeInclusive<u64>> {
         .collect()
 }
 
-fn part1(input: String) {
-    let ranges = parse_input(&input);
-    let mut sum = 0;
-    for e in ranges.into_iter().flatten() {
-        let width = e.ilog10() + 1;
-        if width % 2 == 0 {
-            let top = 10u64.pow(width / 2);
-            if e / top == e % top {
-                sum += e;
+/// How often the block of an invalid ID may be repeated
+#[derive(Clone, Copy)]
+enum Repetitions {
+    Exactly(u32),
+    AtLeast(u32),
+}
+
+impl Repetitions {
+    fn allows(self, reps: u32) -> bool {
+        match self {
+            Repetitions::Exactly(n) => reps == n,
+            Repetitions::AtLeast(n) => reps >= n,
+        }
+    }
+
+    /// Lengths of the blocks that numbers of `len` digits can consist of
+    fn blocks(self, len: u32) -> impl Iterator<Item = u32> {
+        (2..=len)
+            .filter(move |&r| len.is_multiple_of(r) && self.allows(r))
+            .map(move |r| len / r)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Tally {
+    count: u128,
+    sum: u128,
+}
+
+impl AddAssign for Tally {
+    fn add_assign(&mut self, other: Tally) {
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+}
+
+impl SubAssign for Tally {
+    fn sub_assign(&mut self, other: Tally) {
+        self.count -= other.count;
+        self.sum -= other.sum;
+    }
+}
+
+fn digits(n: u64) -> u32 {
+    n.checked_ilog10().unwrap_or(0) + 1
+}
+
+/// Multiplier repeating a block of `block` digits to `len` digits, like
+/// 1001001 for blocks of 3 and 9 digits in total. Also returns the range of
+/// possible blocks.
+fn multiplier(len: u32, block: u32) -> (u128, RangeInclusive<u128>) {
+    let m = (10u128.pow(len) - 1) / (10u128.pow(block) - 1);
+    (m, 10u128.pow(block - 1)..=10u128.pow(block) - 1)
+}
+
+/// Blocks that give numbers inside of `range` when multiplied by `m`
+fn block_range(
+    range: &RangeInclusive<u64>,
+    m: u128,
+    blocks: RangeInclusive<u128>,
+) -> RangeInclusive<u128> {
+    let lo = (*range.start() as u128).div_ceil(m).max(*blocks.start());
+    let hi = (*range.end() as u128 / m).min(*blocks.end());
+    lo..=hi
+}
+
+/// All numbers of `len` digits in `range` that repeat a block of `block` digits
+fn periodic(range: &RangeInclusive<u64>, len: u32, block: u32) -> Tally {
+    let (m, blocks) = multiplier(len, block);
+    let xs = block_range(range, m, blocks);
+    if xs.is_empty() {
+        return Tally::default();
+    }
+    let (lo, hi) = xs.into_inner();
+    let count = hi - lo + 1;
+    Tally {
+        count,
+        sum: m * ((lo + hi) * count / 2),
+    }
+}
+
+/// Count and sum of the invalid IDs in `range` without looking at the IDs
+/// one by one.
+///
+/// A number that repeats a block of `b` digits also repeats a block of any
+/// multiple of `b` digits dividing its length. So for every possible block
+/// length, the numbers whose shortest block has exactly that length are
+/// found by subtracting those with shorter blocks dividing it. Each invalid
+/// ID is then counted once, by its shortest block.
+fn tally(range: &RangeInclusive<u64>, reps: Repetitions) -> Tally {
+    let mut total = Tally::default();
+    for len in digits(*range.start())..=digits(*range.end()) {
+        let allowed: Vec<u32> = reps.blocks(len).collect();
+        let mut shortest = vec![Tally::default(); len as usize];
+        for d in (1..len).filter(|&d| len.is_multiple_of(d)) {
+            let mut t = periodic(range, len, d);
+            for e in (1..d).filter(|&e| d.is_multiple_of(e)) {
+                t -= shortest[e as usize];
+            }
+            shortest[d as usize] = t;
+            if allowed.iter().any(|b| b.is_multiple_of(d)) {
+                total += t;
             }
         }
     }
-    println!("{sum}");
+    total
 }
 
-fn part2(input: String) {
-    let ranges = parse_input(&input);
+/// All invalid IDs in `range`, in increasing order
+fn invalid_ids(range: &RangeInclusive<u64>, reps: Repetitions) -> Vec<u128> {
+    let mut ids = Vec::new();
+    for len in digits(*range.start())..=digits(*range.end()) {
+        for block in reps.blocks(len) {
+            let (m, blocks) = multiplier(len, block);
+            ids.extend(block_range(range, m, blocks).map(|x| x * m));
+        }
+    }
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+fn solve(input: &str, reps: Repetitions) {
+    let verbose = util::verbose();
     let mut sum = 0;
-    'nums: for e in ranges.into_iter().flatten() {
-        let width = e.ilog10() + 1;
-        for rep in 2..=width {
-            if width % rep == 0 {
-                let top = 10u64.pow(width / rep);
-                let mut a = e;
-                let lowest = a % top;
-                let mut invalid = true;
-                while a > top {
-                    a /= top;
-                    if a % top != lowest {
-                        invalid = false;
-                        break;
-                    }
-                }
-                if invalid {
-                    sum += e;
-                    // Don't check other numbers of repetitions
-                    continue 'nums;
-                }
+    for range in parse_input(input) {
+        let t = tally(&range, reps);
+        if verbose {
+            print!("{}-{}: {} invalid", range.start(), range.end(), t.count);
+            if t.count <= MAX_PRINT {
+                print!(" {:?}", invalid_ids(&range, reps));
             }
+            println!();
         }
+        sum += t.sum;
     }
     println!("{sum}");
 }
 
+fn part1(input: String) {
+    solve(&input, Repetitions::Exactly(2));
+}
+
+fn part2(input: String) {
+    solve(&input, Repetitions::AtLeast(2));
+}
+
 util::aoc_main!();
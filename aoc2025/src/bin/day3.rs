@@ -1,71 +1,72 @@
-fn part1(input: String) {
-    let mut sum = 0;
-    'banks: for l in input.lines() {
-        let mut sorted: Vec<(usize, u32)> = l
-            .chars()
-            .map(|c| c.to_digit(10).unwrap())
-            .enumerate()
-            .collect();
-        sorted.sort_by(|(_, a), (_, b)| a.cmp(b).reverse());
-        for (idx, first) in &sorted {
-            for (id2, second) in &sorted {
-                if id2 > idx {
-                    sum += first * 10 + second;
-                    continue 'banks;
-                }
-            }
+use std::fmt::Display;
+use std::iter::Sum;
+use std::ops::{Add, Mul};
+
+use num::BigUint;
+
+/// Totals of up to this many digits per bank are summed in a `u128`
+const MAX_SMALL_DIGITS: usize = 19;
+
+/// Positions of the `k` batteries forming the largest joltage, i.e. the
+/// lexicographically largest subsequence of length `k`. A smaller digit on
+/// the stack is replaced by a larger one as long as enough digits remain.
+fn select(bank: &[u8], k: usize) -> Option<Vec<usize>> {
+    let mut drops = bank.len().checked_sub(k)?;
+    let mut stack: Vec<usize> = Vec::with_capacity(bank.len());
+    for (i, &d) in bank.iter().enumerate() {
+        while drops > 0 && stack.last().is_some_and(|&top| bank[top] < d) {
+            stack.pop();
+            drops -= 1;
         }
+        stack.push(i);
     }
-    println!("{sum}");
+    stack.truncate(k);
+    Some(stack)
 }
 
-// Recursive implementation of greedy algorithm.
-// Returns Vec of length 12 if a result was found, guaranteed to be optimal.
-// If there is no solution with the input, a shorter Vec is returned.
-fn recursive(bank: &[(usize, u32)], mut cur: Vec<(usize, u32)>) -> Vec<(usize, u32)> {
-    let pos = cur.last().unwrap().0;
-    for &(idx, e) in bank.iter().filter(|(idx, _)| *idx > pos) {
-        cur.push((idx, e));
-        if cur.len() == 12 {
-            // Recursion anchor: We have filled all 12 spots and therefore found
-            // the best solution
-            return cur;
-        }
-        // Recurse
-        cur = recursive(bank, cur);
-        if cur.len() == 12 {
-            // Result found
-            return cur;
-        }
-        // Nothing found, try next in this position
-        cur.pop();
-    }
-    // Unsuccessful search with given inputs
-    cur
+fn value<T>(digits: impl Iterator<Item = u8>) -> T
+where
+    T: From<u8> + Add<Output = T> + Mul<Output = T>,
+{
+    digits.fold(T::from(0), |acc, d| acc * T::from(10) + T::from(d))
 }
 
-fn part2(input: String) {
-    let mut sum = 0;
-    'banks: for l in input.lines() {
-        let mut sorted: Vec<(usize, u32)> = l
-            .chars()
-            .map(|c| c.to_digit(10).unwrap())
-            .enumerate()
-            .collect();
-        sorted.sort_by(|(_, a), (_, b)| a.cmp(b).reverse());
-        let mut cur: Vec<(usize, u32)> = Vec::with_capacity(12);
-        for &(idx, first) in &sorted {
-            cur.push((idx, first));
-            cur = recursive(&sorted, cur);
-            if cur.len() == 12 {
-                let num = cur.iter().fold(0u64, |acc, e| acc * 10 + e.1 as u64);
-                sum += num;
-                continue 'banks;
+fn total<T>(input: &str, k: usize) -> T
+where
+    T: From<u8> + Add<Output = T> + Mul<Output = T> + Sum + Display,
+{
+    let verbose = util::verbose();
+    input
+        .lines()
+        .filter_map(|l| {
+            let bank: Vec<u8> = l.bytes().map(|b| b - b'0').collect();
+            let Some(positions) = select(&bank, k) else {
+                eprintln!("Bank {l} has fewer than {k} batteries");
+                return None;
+            };
+            let joltage: T = value(positions.iter().map(|&i| bank[i]));
+            if verbose {
+                println!("{l}: {joltage} from {positions:?}");
             }
-            cur.pop();
-        }
+            Some(joltage)
+        })
+        .sum()
+}
+
+fn solve(input: &str, k: usize) {
+    if k <= MAX_SMALL_DIGITS {
+        println!("{}", total::<u128>(input, k));
+    } else {
+        println!("{}", total::<BigUint>(input, k));
     }
-    println!("{sum}");
+}
+
+fn part1(input: String) {
+    solve(&input, 2);
+}
+
+fn part2(input: String) {
+    solve(&input, 12);
 }
 
 util::aoc_main!();
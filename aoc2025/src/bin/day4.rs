@@ -1,3 +1,6 @@
+/// Rolls with fewer than this many neighboring rolls can be removed
+const THRESHOLD: usize = 4;
+
 fn parse_input(input: &str) -> Vec<Vec<bool>> {
     input
         .lines()
@@ -5,60 +8,95 @@ fn parse_input(input: &str) -> Vec<Vec<bool>> {
         .collect()
 }
 
-fn count_adj(grid: &[Vec<bool>], (x, y): (usize, usize)) -> usize {
-    let width = grid[0].len();
-    let height = grid.len();
-    grid.iter()
-        .take((y + 2).min(height))
-        .skip(y.saturating_sub(1))
-        .map(|r| {
-            r.iter()
-                .take((x + 2).min(width))
-                .skip(x.saturating_sub(1))
-                .take(3)
-                .filter(|e| **e)
-                .count()
-        })
-        .sum::<usize>()
+fn neighbors(
+    (x, y): (usize, usize),
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = (usize, usize)> {
+    (y.saturating_sub(1)..(y + 2).min(height))
+        .flat_map(move |ny| (x.saturating_sub(1)..(x + 2).min(width)).map(move |nx| (nx, ny)))
+        .filter(move |&p| p != (x, y))
 }
 
-fn part1(input: String) {
-    let grid = parse_input(&input);
-    let mut count = 0u32;
+/// Wave in which each roll is removed, starting at 1, when all accessible
+/// rolls are removed at once over and over. `None` for rolls that stay.
+///
+/// Neighbor counts are kept up to date while removing, and only neighbors
+/// of removed rolls can become accessible in the next wave.
+fn peel(grid: &[Vec<bool>], threshold: usize) -> Vec<Vec<Option<usize>>> {
+    let (width, height) = (grid[0].len(), grid.len());
+    let mut present = grid.to_vec();
+    let mut count = vec![vec![0; width]; height];
+    let mut current = Vec::new();
     for (y, row) in grid.iter().enumerate() {
-        for (x, _) in row.iter().enumerate().filter(|(_, r)| **r) {
-            let n_adj = count_adj(&grid, (x, y));
-            // Center roll is counted too
-            if n_adj < 5 {
-                count += 1;
+        for x in (0..width).filter(|&x| row[x]) {
+            count[y][x] = neighbors((x, y), width, height)
+                .filter(|&(nx, ny)| grid[ny][nx])
+                .count();
+            if count[y][x] < threshold {
+                current.push((x, y));
             }
         }
     }
-    println!("{count}");
-}
 
-fn part2(input: String) {
-    let mut grid = parse_input(&input);
-    let mut removed = 0u32;
-    loop {
-        let mut next_grid = grid.clone();
-        let prev_removed = removed;
-        for (y, row) in grid.iter().enumerate() {
-            for (x, _) in row.iter().enumerate().filter(|(_, r)| **r) {
-                let n_adj = count_adj(&grid, (x, y));
-                // Center roll is counted too
-                if n_adj < 5 {
-                    next_grid[y][x] = false;
-                    removed += 1;
+    let mut waves = vec![vec![None; width]; height];
+    let mut wave = 1;
+    while !current.is_empty() {
+        for &(x, y) in &current {
+            present[y][x] = false;
+            waves[y][x] = Some(wave);
+        }
+        let mut next = Vec::new();
+        for &(x, y) in &current {
+            for (nx, ny) in neighbors((x, y), width, height) {
+                if present[ny][nx] {
+                    count[ny][nx] -= 1;
+                    // Remaining rolls had enough neighbors so far, so this
+                    // happens at most once for each
+                    if count[ny][nx] + 1 == threshold {
+                        next.push((nx, ny));
+                    }
                 }
             }
         }
-        if removed == prev_removed {
-            break;
+        current = next;
+        wave += 1;
+    }
+    waves
+}
+
+/// Draw the wave of each removed roll as a digit or letter, repeating
+/// after z. Rolls that stay are drawn as `@`.
+fn heat_map(grid: &[Vec<bool>], waves: &[Vec<Option<usize>>]) -> String {
+    let mut out = String::new();
+    for (row, wave_row) in grid.iter().zip(waves) {
+        for (&roll, wave) in row.iter().zip(wave_row) {
+            out.push(match (roll, wave) {
+                (_, Some(w)) => char::from_digit(((w - 1) % 35 + 1) as u32, 36).unwrap(),
+                (true, None) => '@',
+                (false, None) => '.',
+            });
         }
-        grid = next_grid;
+        out.push('\n');
+    }
+    out
+}
+
+fn part1(input: String) {
+    let grid = parse_input(&input);
+    let waves = peel(&grid, THRESHOLD);
+    let count = waves.iter().flatten().filter(|&&w| w == Some(1)).count();
+    println!("{count}");
+}
+
+fn part2(input: String) {
+    let grid = parse_input(&input);
+    let waves = peel(&grid, THRESHOLD);
+    if util::verbose() {
+        print!("{}", heat_map(&grid, &waves));
     }
-    println!("{}", removed);
+    let removed = waves.iter().flatten().filter(|w| w.is_some()).count();
+    println!("{removed}");
 }
 
 util::aoc_main!();
@@ -1,72 +1,127 @@
+use std::io::{self, BufRead, Write};
 use std::ops::Range;
 
-fn parse_input(input: &str) -> (Vec<Range<u64>>, Vec<u64>) {
-    let ranges: Vec<_> = input
-        .lines()
-        .take_while(|l| !l.is_empty())
-        .map(|l| {
-            let (a, b) = l.split_once('-').unwrap();
-            a.parse().unwrap()..b.parse::<u64>().unwrap() + 1
-        })
-        .collect();
-    let nums = input
-        .lines()
-        .skip(ranges.len() + 1)
-        .map(|n| n.parse().unwrap())
-        .collect();
-    (ranges, nums)
+/// Set of fresh ingredient IDs, stored as sorted ranges that neither
+/// overlap nor touch
+#[derive(Clone, Default)]
+struct FreshDb {
+    ranges: Vec<Range<u64>>,
+}
+
+impl FreshDb {
+    fn from_ranges(ranges: impl IntoIterator<Item = Range<u64>>) -> Self {
+        let mut db = FreshDb::default();
+        for r in ranges {
+            db.insert(r);
+        }
+        db
+    }
+
+    /// Read ranges like `3-5`, one per line, with inclusive ends
+    fn load(reader: impl BufRead) -> io::Result<Self> {
+        let invalid =
+            |l: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Bad range {l}"));
+        let mut ranges = Vec::new();
+        for l in reader.lines() {
+            let l = l?;
+            let (a, b) = l.split_once('-').ok_or_else(|| invalid(&l))?;
+            let start: u64 = a.parse().map_err(|_| invalid(&l))?;
+            let last: u64 = b.parse().map_err(|_| invalid(&l))?;
+            let end = last.checked_add(1).ok_or_else(|| invalid(&l))?;
+            ranges.push(start..end);
+        }
+        Ok(Self::from_ranges(ranges))
+    }
+
+    /// Write the normalized ranges in the format read by `load`
+    fn save(&self, mut writer: impl Write) -> io::Result<()> {
+        for r in &self.ranges {
+            writeln!(writer, "{}-{}", r.start, r.end - 1)?;
+        }
+        Ok(())
+    }
+
+    /// Indices of the stored ranges intersecting `r`
+    fn overlapping(&self, r: &Range<u64>) -> Range<usize> {
+        let lo = self.ranges.partition_point(|x| x.end <= r.start);
+        let hi = self.ranges.partition_point(|x| x.start < r.end);
+        lo..hi.max(lo)
+    }
+
+    fn contains(&self, id: u64) -> bool {
+        let i = self.ranges.partition_point(|x| x.end <= id);
+        i < self.ranges.len() && self.ranges[i].start <= id
+    }
+
+    fn insert(&mut self, r: Range<u64>) {
+        if r.is_empty() {
+            return;
+        }
+        // Ranges that just touch `r` are merged as well
+        let lo = self.ranges.partition_point(|x| x.end < r.start);
+        let hi = self.ranges.partition_point(|x| x.start <= r.end);
+        let mut new = r;
+        if lo < hi {
+            new.start = new.start.min(self.ranges[lo].start);
+            new.end = new.end.max(self.ranges[hi - 1].end);
+        }
+        self.ranges.splice(lo..hi, [new]);
+    }
+
+    fn remove(&mut self, r: Range<u64>) {
+        let idx = self.overlapping(&r);
+        if r.is_empty() || idx.is_empty() {
+            return;
+        }
+        let (first, last) = (self.ranges[idx.start].start, self.ranges[idx.end - 1].end);
+        let left = (first < r.start).then_some(first..r.start);
+        let right = (r.end < last).then_some(r.end..last);
+        self.ranges.splice(idx, left.into_iter().chain(right));
+    }
+
+    /// Number of fresh IDs in `r`
+    fn count(&self, r: Range<u64>) -> u64 {
+        self.ranges[self.overlapping(&r)]
+            .iter()
+            .map(|x| x.end.min(r.end) - x.start.max(r.start))
+            .sum()
+    }
+
+    fn total(&self) -> u64 {
+        self.ranges.iter().map(|r| r.end - r.start).sum()
+    }
+}
+
+fn parse_input(input: &str) -> (FreshDb, Vec<u64>) {
+    let (ranges, nums) = input.split_once("\n\n").unwrap();
+    let db = FreshDb::load(ranges.as_bytes()).unwrap();
+    let nums = nums.lines().map(|n| n.parse().unwrap()).collect();
+    (db, nums)
 }
 
 fn part1(input: String) {
-    let (ranges, nums) = parse_input(&input);
-    let count = nums
-        .iter()
-        .filter(|n| ranges.iter().any(|r| r.contains(n)))
-        .count();
+    let (db, nums) = parse_input(&input);
+    let count = nums.iter().filter(|&&n| db.contains(n)).count();
+    if util::verbose()
+        && let (Some(&min), Some(&max)) = (nums.iter().min(), nums.iter().max())
+    {
+        let fresh = db.count(min..max + 1);
+        println!("Fresh IDs from {min} to {max}: {fresh}");
+    }
     println!("{count}");
 }
 
 fn part2(input: String) {
-    let (ranges, _) = parse_input(&input);
-    // Ranges are added to this Vec always sorted by start and non-overlapping
-    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
-    for r in ranges {
-        // Find index of first intersecting range
-        let first_int_o = merged.iter().position(|m| {
-            // Intersection range (if any)
-            let int_start = r.start.max(m.start);
-            let int_end = r.end.min(m.end);
-            int_start < int_end
-        });
-        if let Some(first_int) = first_int_o {
-            // Exclusive
-            let last_int = merged.len()
-                - merged
-                    .iter()
-                    .rev()
-                    .position(|m| {
-                        let int_start = r.start.max(m.start);
-                        let int_end = r.end.min(m.end);
-                        int_start < int_end
-                    })
-                    .unwrap();
-            // New range replacing all intersecting ranges
-            let new = r.start.min(merged[first_int].start)..r.end.max(merged[last_int - 1].end);
-            merged[first_int] = new;
-            for i in (first_int + 1)..last_int {
-                merged.remove(i);
-            }
-        } else {
-            // Does not overlap with anything. Find index that keeps sorting
-            let idx = merged
-                .iter()
-                .position(|m| m.start > r.start)
-                .unwrap_or(merged.len());
-            merged.insert(idx, r);
+    let (db, nums) = parse_input(&input);
+    if util::verbose() {
+        db.save(io::stdout().lock()).unwrap();
+        let mut missing = db.clone();
+        for &n in &nums {
+            missing.remove(n..n + 1);
         }
+        println!("Fresh IDs not available: {}", missing.total());
     }
-    let count = merged.iter().map(|r| r.end - r.start).sum::<u64>();
-    println!("{count}");
+    println!("{}", db.total());
 }
 
 util::aoc_main!();
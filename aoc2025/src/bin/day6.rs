@@ -1,57 +1,142 @@
-fn part1(input: String) {
-    let mut nums: Vec<Vec<u64>> = Vec::new();
-    let mut mul: Vec<bool> = Vec::new();
-    for l in input.lines() {
-        if l.chars().next().unwrap().is_ascii_digit() {
-            let row = l
-                .split_ascii_whitespace()
-                .map(|s| s.parse::<u64>().unwrap())
-                .collect();
-            nums.push(row);
-        } else {
-            mul = l.split_ascii_whitespace().map(|s| s == "*").collect();
+struct Operator {
+    symbol: char,
+    identity: u64,
+    apply: fn(u64, u64) -> u64,
+}
+
+const ADD: Operator = Operator {
+    symbol: '+',
+    identity: 0,
+    apply: |a, b| a + b,
+};
+const MUL: Operator = Operator {
+    symbol: '*',
+    identity: 1,
+    apply: |a, b| a * b,
+};
+
+const OPERATORS: &[Operator] = &[ADD, MUL];
+
+#[derive(Clone, Copy)]
+enum Reading {
+    /// Each row is a number
+    Rows,
+    /// Each column is a number, top digit first, starting from the right
+    Columns,
+}
+
+/// Block of digit rows between two blank columns, padded to the same width
+struct Problem {
+    digits: Vec<Vec<u8>>,
+    symbol: char,
+}
+
+impl Problem {
+    fn numbers(&self, reading: Reading) -> Vec<u64> {
+        let parse = |digits: &mut dyn Iterator<Item = u8>| {
+            digits.filter(u8::is_ascii_digit).fold(None, |acc, d| {
+                Some(acc.unwrap_or(0) * 10 + (d - b'0') as u64)
+            })
+        };
+        match reading {
+            Reading::Rows => self
+                .digits
+                .iter()
+                .filter_map(|row| parse(&mut row.iter().copied()))
+                .collect(),
+            Reading::Columns => (0..self.digits[0].len())
+                .rev()
+                .filter_map(|x| parse(&mut self.digits.iter().map(|row| row[x])))
+                .collect(),
         }
     }
-    let mut sum = 0;
-    for (idx, op_mul) in mul.iter().enumerate() {
-        let col = nums.iter().map(|row| row[idx]);
-        sum += if *op_mul {
-            col.reduce(|acc, n| acc * n)
-        } else {
-            col.reduce(|acc, n| acc + n)
-        }
-        .unwrap();
+
+    fn evaluate(&self, reading: Reading, operators: &[Operator]) -> Result<u64, String> {
+        let op = operators
+            .iter()
+            .find(|op| op.symbol == self.symbol)
+            .ok_or_else(|| format!("Unknown operator {}", self.symbol))?;
+        Ok(self
+            .numbers(reading)
+            .into_iter()
+            .fold(op.identity, op.apply))
+    }
+
+    /// The problem written on one line, like `123 * 45 * 6`
+    fn equation(&self, reading: Reading) -> String {
+        let numbers: Vec<String> = self.numbers(reading).iter().map(u64::to_string).collect();
+        numbers.join(&format!(" {} ", self.symbol))
     }
-    println!("{sum}");
 }
 
-fn part2(input: String) {
-    let grid: Vec<&[u8]> = input.lines().map(|l| l.as_bytes()).collect();
-    let n_rows = grid.len() - 1; // Not counting operator row
-    let mut op_mul = grid[n_rows][0] == b'*';
-    let mut cur = if op_mul { 1 } else { 0 };
-    let mut sum = 0;
-    for x in 0..grid[0].len() {
-        let digits: Vec<u8> = (0..n_rows).map(|y| grid[y][x]).collect();
-        if digits.iter().all(|d| *d == b' ') {
-            sum += cur;
-            op_mul = grid[n_rows][x + 1] == b'*';
-            cur = if op_mul { 1 } else { 0 };
-            continue;
+struct Worksheet {
+    problems: Vec<Problem>,
+}
+
+impl Worksheet {
+    /// Split the column-aligned sheet into problems at columns that are
+    /// blank in every line. The operator of each problem is in the last line.
+    fn parse(input: &str) -> Result<Self, String> {
+        let lines: Vec<&[u8]> = input.lines().map(str::as_bytes).collect();
+        let Some((ops, rows)) = lines.split_last() else {
+            return Err("Empty worksheet".to_owned());
+        };
+        let width = lines.iter().map(|l| l.len()).max().unwrap();
+        let at = |l: &[u8], x: usize| l.get(x).copied().unwrap_or(b' ');
+        let blank = |x: usize| lines.iter().all(|l| at(l, x) == b' ');
+
+        let mut problems = Vec::new();
+        let mut start = 0;
+        for end in 0..=width {
+            if end < width && !blank(end) {
+                continue;
+            }
+            if start < end {
+                let symbol = (start..end)
+                    .map(|x| at(ops, x))
+                    .find(|&b| b != b' ')
+                    .ok_or_else(|| format!("No operator for column {start}"))?;
+                let digits = rows
+                    .iter()
+                    .map(|l| (start..end).map(|x| at(l, x)).collect())
+                    .collect();
+                problems.push(Problem {
+                    digits,
+                    symbol: symbol as char,
+                });
+            }
+            start = end + 1;
         }
-        let n = String::from_utf8(digits)
-            .unwrap()
-            .trim()
-            .parse::<u64>()
-            .unwrap();
-        if op_mul {
-            cur *= n;
-        } else {
-            cur += n;
+        Ok(Worksheet { problems })
+    }
+
+    fn grand_total(&self, reading: Reading, operators: &[Operator]) -> Result<u64, String> {
+        let verbose = util::verbose();
+        let mut total = 0;
+        for p in &self.problems {
+            let result = p.evaluate(reading, operators)?;
+            if verbose {
+                println!("{} = {result}", p.equation(reading));
+            }
+            total += result;
         }
+        Ok(total)
+    }
+}
+
+fn solve(input: &str, reading: Reading) {
+    match Worksheet::parse(input).and_then(|w| w.grand_total(reading, OPERATORS)) {
+        Ok(total) => println!("{total}"),
+        Err(e) => eprintln!("{e}"),
     }
-    sum += cur;
-    println!("{sum}");
+}
+
+fn part1(input: String) {
+    solve(&input, Reading::Rows);
+}
+
+fn part2(input: String) {
+    solve(&input, Reading::Columns);
 }
 
 util::aoc_main!();
@@ -1,51 +1,163 @@
-use std::collections::VecDeque;
-
-fn parse_input(input: &str) -> (Vec<Vec<bool>>, (usize, usize)) {
-    let splits = input
-        .lines()
-        .map(|l| l.chars().map(|c| c == '^').collect())
-        .collect();
-    // Assume start is on first row
-    let start = (input.chars().position(|c| c == 'S').unwrap(), 0);
-    (splits, start)
-}
-
-fn solve(input: String) {
-    let (splits, start) = parse_input(&input);
-    let mut nsplits = 0u32;
-    let mut timelines = 1u64;
-    let mut frontier = VecDeque::from([(start, 1)]);
-    while let Some((pos, multiplicity)) = frontier.pop_front() {
-        let (x, y) = (pos.0, pos.1 + 1);
-        if y == splits.len() {
-            // Falls out of bottom
-            continue;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use num::BigUint;
+
+/// Splitter symbols and the columns, relative to the splitter, of the beams
+/// they fork into
+const SPLITTERS: &[(u8, &[isize])] = &[(b'^', &[-1, 1]), (b'*', &[-1, 0, 1])];
+const EMITTER: u8 = b'S';
+
+/// Number of timelines, switching to arbitrary precision once it no longer
+/// fits in a `u64`
+#[derive(Clone, Debug)]
+enum Count {
+    Small(u64),
+    Big(BigUint),
+}
+
+impl Count {
+    const ZERO: Count = Count::Small(0);
+
+    fn is_zero(&self) -> bool {
+        matches!(self, Count::Small(0))
+    }
+
+    fn add(&mut self, other: &Count) {
+        match (&mut *self, other) {
+            (Count::Small(a), Count::Small(b)) => {
+                if let Some(sum) = a.checked_add(*b) {
+                    *a = sum;
+                } else {
+                    *self = Count::Big(BigUint::from(*a) + *b);
+                }
+            }
+            (Count::Small(a), Count::Big(b)) => *self = Count::Big(b + *a),
+            (Count::Big(a), Count::Small(b)) => *a += *b,
+            (Count::Big(a), Count::Big(b)) => *a += b,
+        }
+    }
+}
+
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Count::Small(n) => write!(f, "{n}"),
+            Count::Big(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+struct Manifold {
+    grid: Vec<Vec<u8>>,
+}
+
+struct Simulation {
+    /// Number of timelines with a beam in each cell, by row
+    beams: Vec<Vec<Count>>,
+    /// Splitters reached by at least one beam, as (x, y)
+    hit: BTreeSet<(usize, usize)>,
+    /// Timelines whose beam left the manifold
+    timelines: Count,
+}
+
+impl Manifold {
+    fn parse(input: &str) -> Self {
+        let grid: Vec<Vec<u8>> = input.lines().map(|l| l.bytes().collect()).collect();
+        Manifold { grid }
+    }
+
+    fn forks(&self, x: usize, y: usize) -> Option<&'static [isize]> {
+        let cell = self.grid[y][x];
+        SPLITTERS.iter().find(|s| s.0 == cell).map(|s| s.1)
+    }
+
+    /// Send beams down from every emitter, one row at a time. Beams that
+    /// reach the same cell are merged, adding up their timelines.
+    fn simulate(&self) -> Simulation {
+        let mut beams: Vec<Vec<Count>> = Vec::with_capacity(self.grid.len());
+        let mut hit = BTreeSet::new();
+        let mut timelines = Count::ZERO;
+        let mut incoming: Vec<Count> = Vec::new();
+        for (y, row) in self.grid.iter().enumerate() {
+            let width = row.len();
+            incoming.resize(width.max(incoming.len()), Count::ZERO);
+            // Beams falling past the end of a shorter row leave the manifold
+            for m in incoming.drain(width..) {
+                timelines.add(&m);
+            }
+            let mut cur = vec![Count::ZERO; width];
+            for (x, m) in incoming.iter().enumerate() {
+                if m.is_zero() {
+                    continue;
+                }
+                let Some(forks) = self.forks(x, y) else {
+                    cur[x].add(m);
+                    continue;
+                };
+                hit.insert((x, y));
+                for &dx in forks {
+                    match x.checked_add_signed(dx).filter(|&nx| nx < width) {
+                        Some(nx) => cur[nx].add(m),
+                        None => timelines.add(m),
+                    }
+                }
+            }
+            for (x, &cell) in row.iter().enumerate() {
+                if cell == EMITTER {
+                    cur[x].add(&Count::Small(1));
+                }
+            }
+            incoming = cur.clone();
+            beams.push(cur);
+        }
+        for m in &incoming {
+            timelines.add(m);
         }
-        if splits[y][x] {
-            nsplits += 1;
-            timelines += multiplicity;
-            if let Some((b, m2)) = frontier.back_mut()
-                && *b == (x - 1, y)
-            {
-                *m2 += multiplicity;
-            } else {
-                frontier.push_back(((x - 1, y), multiplicity));
+        Simulation {
+            beams,
+            hit,
+            timelines,
+        }
+    }
+
+    /// The manifold with beams drawn as `|`
+    fn render(&self, sim: &Simulation) -> String {
+        let mut out = String::new();
+        for (row, beams) in self.grid.iter().zip(&sim.beams) {
+            for (&cell, m) in row.iter().zip(beams) {
+                out.push(if cell == b'.' && !m.is_zero() {
+                    '|'
+                } else {
+                    cell as char
+                });
             }
-            frontier.push_back(((x + 1, y), multiplicity));
-        } else if let Some((b, m2)) = frontier.back_mut()
-            && *b == (x, y)
-        {
-            *m2 += multiplicity;
-        } else {
-            frontier.push_back(((x, y), multiplicity));
+            out.push('\n');
         }
+        out
     }
-    println!("Part 1: {nsplits}");
-    println!("Part 2: {timelines}");
 }
 
-fn main() -> std::io::Result<()> {
-    let (input, _) = util::get_input("day7.txt")?;
-    solve(input);
-    Ok(())
+fn part1(input: String) {
+    let manifold = Manifold::parse(&input);
+    let sim = manifold.simulate();
+    if util::verbose() {
+        print!("{}", manifold.render(&sim));
+    }
+    println!("{}", sim.hit.len());
 }
+
+fn part2(input: String) {
+    let manifold = Manifold::parse(&input);
+    let sim = manifold.simulate();
+    if util::verbose() {
+        for (y, row) in sim.beams.iter().enumerate() {
+            let mut total = Count::ZERO;
+            row.iter().for_each(|m| total.add(m));
+            println!("Row {y}: {total} timelines");
+        }
+    }
+    println!("{}", sim.timelines);
+}
+
+util::aoc_main!();
@@ -1,6 +1,11 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
 use euclid::default::Point3D;
 use euclid::point3;
 
+const CONNECTIONS: usize = 1000;
+
 fn parse_input(input: &str) -> Vec<Point3D<i64>> {
     input
         .lines()
@@ -16,84 +21,223 @@ fn parse_input(input: &str) -> Vec<Point3D<i64>> {
         .collect()
 }
 
-// Distances between all points. Reflexive and symmetric pairs are skipped,
-// so the Vec's have increasing size, starting at 0.
-fn dists(points: &[Point3D<i64>]) -> Vec<Vec<i64>> {
-    points
-        .iter()
-        .enumerate()
-        .map(|(idx, &p1)| {
-            points
-                .iter()
-                .take(idx)
-                .map(|&p2| (p2 - p1).square_length())
-                .collect::<Vec<i64>>()
-        })
-        .collect()
+/// Disjoint-set forest with union by size and path compression
+struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    n_sets: usize,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> Self {
+        DisjointSet {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+            n_sets: n,
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // Point everything on the way directly to the root
+        let mut cur = x;
+        while self.parent[cur] != root {
+            cur = std::mem::replace(&mut self.parent[cur], root);
+        }
+        root
+    }
+
+    /// Merge the sets of `a` and `b`. Returns false if they were already the same.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            (a, b) = (b, a);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        self.n_sets -= 1;
+        true
+    }
+
+    /// Sizes of all sets
+    fn sizes(&mut self) -> Vec<usize> {
+        let roots: Vec<usize> = (0..self.parent.len())
+            .filter(|&x| self.find(x) == x)
+            .collect();
+        roots.into_iter().map(|x| self.size[x]).collect()
+    }
 }
 
-fn sorted_distances(dists: &[Vec<i64>]) -> Vec<(usize, usize, i64)> {
-    let mut sorted: Vec<(usize, usize, i64)> = dists
-        .iter()
-        .enumerate()
-        .flat_map(|(i, row)| row.iter().enumerate().map(move |(j, d)| (i, j, *d)))
-        .collect();
-    sorted.sort_by_key(|(_, _, d)| *d);
-    sorted
+fn coord(p: Point3D<i64>, axis: usize) -> i64 {
+    match axis {
+        0 => p.x,
+        1 => p.y,
+        _ => p.z,
+    }
 }
 
-fn part1(input: String) {
-    let points = parse_input(&input);
-    let d = dists(&points);
-    let sorted = sorted_distances(&d);
-
-    let mut circuits: Vec<u32> = (0..points.len() as u32).collect();
-    for (i, j, _) in sorted.into_iter().take(1000) {
-        let new_circuit = circuits[i];
-        let old_circuit = circuits[j];
-        if new_circuit != old_circuit {
-            for c in circuits.iter_mut() {
-                if *c == old_circuit {
-                    *c = new_circuit;
+/// Implicit k-d tree. Each range of `order` is split at its middle element
+/// by the coordinate `depth % 3`.
+struct KdTree<'a> {
+    points: &'a [Point3D<i64>],
+    order: Vec<usize>,
+}
+
+impl<'a> KdTree<'a> {
+    fn new(points: &'a [Point3D<i64>]) -> Self {
+        fn build(order: &mut [usize], points: &[Point3D<i64>], depth: usize) {
+            if order.len() <= 1 {
+                return;
+            }
+            let mid = order.len() / 2;
+            order.select_nth_unstable_by_key(mid, |&i| coord(points[i], depth % 3));
+            let (left, right) = order.split_at_mut(mid);
+            build(left, points, depth + 1);
+            build(&mut right[1..], points, depth + 1);
+        }
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        build(&mut order, points, 0);
+        KdTree { points, order }
+    }
+
+    fn neighbors(&self, query: usize) -> Neighbors<'_> {
+        let heap = BinaryHeap::from([Reverse((0, Item::Node(0, self.order.len(), 0)))]);
+        Neighbors {
+            tree: self,
+            query,
+            heap,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Item {
+    Point(usize),
+    /// Range in the tree's order and depth
+    Node(usize, usize, usize),
+}
+
+/// Iterator over all other points by increasing distance from `query`.
+/// Subtrees wait in the heap with a lower bound of their distance, and are
+/// only expanded once nothing closer is left.
+struct Neighbors<'a> {
+    tree: &'a KdTree<'a>,
+    query: usize,
+    heap: BinaryHeap<Reverse<(i64, Item)>>,
+}
+
+impl Iterator for Neighbors<'_> {
+    /// Squared distance and point index
+    type Item = (i64, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let q = self.tree.points[self.query];
+        while let Some(Reverse((dist, item))) = self.heap.pop() {
+            match item {
+                Item::Point(p) if p == self.query => {}
+                Item::Point(p) => return Some((dist, p)),
+                Item::Node(lo, hi, _) if lo >= hi => {}
+                Item::Node(lo, hi, depth) => {
+                    let mid = lo + (hi - lo) / 2;
+                    let p = self.tree.order[mid];
+                    let point = self.tree.points[p];
+                    self.heap
+                        .push(Reverse(((point - q).square_length(), Item::Point(p))));
+                    let diff = coord(q, depth % 3) - coord(point, depth % 3);
+                    let (near, far) = if diff < 0 {
+                        ((lo, mid), (mid + 1, hi))
+                    } else {
+                        ((mid + 1, hi), (lo, mid))
+                    };
+                    self.heap
+                        .push(Reverse((dist, Item::Node(near.0, near.1, depth + 1))));
+                    // The far side is at least as far away as the splitting plane
+                    let far_dist = dist.max(diff * diff);
+                    self.heap
+                        .push(Reverse((far_dist, Item::Node(far.0, far.1, depth + 1))));
                 }
             }
         }
+        None
+    }
+}
+
+/// All pairs of points by increasing distance, merged from the neighbor
+/// iterators of every point.
+fn closest_pairs<'a>(tree: &'a KdTree<'a>) -> impl Iterator<Item = (usize, usize)> + 'a {
+    let mut neighbors: Vec<Neighbors> = (0..tree.points.len()).map(|i| tree.neighbors(i)).collect();
+    let mut heap = BinaryHeap::new();
+    for (i, n) in neighbors.iter_mut().enumerate() {
+        if let Some((dist, j)) = n.next() {
+            heap.push(Reverse((dist, i, j)));
+        }
     }
-    let mut sizes: Vec<u32> = vec![0; points.len()];
-    for c in circuits {
-        sizes[c as usize] += 1
+    std::iter::from_fn(move || {
+        while let Some(Reverse((_, i, j))) = heap.pop() {
+            if let Some((dist, next)) = neighbors[i].next() {
+                heap.push(Reverse((dist, i, next)));
+            }
+            // Every pair comes up once from each side
+            if i < j {
+                return Some((i, j));
+            }
+        }
+        None
+    })
+}
+
+/// Connect the closest pairs of junction boxes, like Kruskal's algorithm.
+/// Returns the circuits and the connections that joined two of them, which
+/// form a minimum spanning tree once everything is connected.
+fn connect(points: &[Point3D<i64>], limit: Option<usize>) -> (DisjointSet, Vec<(usize, usize)>) {
+    let tree = KdTree::new(points);
+    let mut circuits = DisjointSet::new(points.len());
+    let mut tree_edges = Vec::new();
+    for (i, j) in closest_pairs(&tree).take(limit.unwrap_or(usize::MAX)) {
+        if circuits.union(i, j) {
+            tree_edges.push((i, j));
+            if circuits.n_sets == 1 {
+                break;
+            }
+        }
     }
+    (circuits, tree_edges)
+}
+
+fn print_edges(points: &[Point3D<i64>], edges: &[(usize, usize)]) {
+    for &(i, j) in edges {
+        let (a, b) = (points[i], points[j]);
+        println!("{},{},{} {},{},{}", a.x, a.y, a.z, b.x, b.y, b.z);
+    }
+}
+
+fn part1(input: String) {
+    let points = parse_input(&input);
+    let (mut circuits, edges) = connect(&points, Some(CONNECTIONS));
+    if util::verbose() {
+        print_edges(&points, &edges);
+    }
+    let mut sizes = circuits.sizes();
     sizes.sort_unstable();
-    let result = sizes.iter().rev().take(3).product::<u32>();
+    let result = sizes.iter().rev().take(3).product::<usize>();
     println!("{result}");
 }
 
 fn part2(input: String) {
     let points = parse_input(&input);
-    let d = dists(&points);
-    let sorted = sorted_distances(&d);
-
-    let mut circuits: Vec<u32> = (0..points.len() as u32).collect();
-    for (i, j, _) in sorted.into_iter() {
-        let new_circuit = circuits[i];
-        let old_circuit = circuits[j];
-        if new_circuit != old_circuit {
-            let mut all_connected = true;
-            for c in circuits.iter_mut() {
-                if *c == old_circuit {
-                    *c = new_circuit;
-                }
-                if *c != new_circuit {
-                    all_connected = false;
-                }
-            }
-            if all_connected {
-                let result = points[i].x * points[j].x;
-                println!("{result}");
-                return;
-            }
-        }
+    let (_, edges) = connect(&points, None);
+    if util::verbose() {
+        print_edges(&points, &edges);
     }
+    let &(i, j) = edges.last().unwrap();
+    let result = points[i].x * points[j].x;
+    println!("{result}");
 }
 
 util::aoc_main!();
@@ -1,6 +1,7 @@
-use std::ops::{Range, RangeInclusive};
+/// Tile coordinates as (x, y)
+type Tile = (u32, u32);
 
-fn parse_input(input: &str) -> Vec<(u32, u32)> {
+fn parse_input(input: &str) -> Vec<Tile> {
     input
         .lines()
         .map(|l| {
@@ -11,7 +12,7 @@ fn parse_input(input: &str) -> Vec<(u32, u32)> {
 }
 
 #[inline]
-fn area(a: (u32, u32), b: (u32, u32)) -> u64 {
+fn area(a: Tile, b: Tile) -> u64 {
     (a.0.abs_diff(b.0) as u64 + 1) * (a.1.abs_diff(b.1) as u64 + 1)
 }
 
@@ -29,119 +30,210 @@ fn part1(input: String) {
     println!("{largest}");
 }
 
-// Returns true only if t is not inside of the rectangle
-#[inline]
-fn red_allowed(t: (u32, u32), x_range: Range<u32>, y_range: Range<u32>) -> bool {
-    !(t.0 > x_range.start && t.0 + 1 < x_range.end && t.1 > y_range.start && t.1 + 1 < y_range.end)
+/// Edge between two consecutive corners, with `from <= to`
+#[derive(Clone, Copy)]
+enum Edge {
+    Vertical { x: u32, from: u32, to: u32 },
+    Horizontal { y: u32, from: u32, to: u32 },
 }
 
-fn is_contained(
-    a: (u32, u32),
-    b: (u32, u32),
-    tiles_x: &[(u32, u32)],
-    tiles_y: &[(u32, u32)],
-    vert_lines: &[(u32, RangeInclusive<u32>)],
-    hori_lines: &[(u32, RangeInclusive<u32>)],
-) -> bool {
-    let x_range = a.0.min(b.0)..(a.0.max(b.0) + 1);
-    let y_range = a.1.min(b.1)..(a.1.max(b.1) + 1);
-
-    // Check that no corners (red tiles) are inside the rectangle
-    let corners_ok = if x_range.end - x_range.start <= y_range.end - y_range.start {
-        // Use tiles_x
-        let start = match tiles_x.binary_search(&(x_range.start, 0)) {
-            Ok(e) => e,
-            Err(e) => e,
-        };
-        tiles_x
-            .iter()
-            .skip(start)
-            .take_while(|t| t.0 < x_range.end)
-            .filter(|&&t| t != a && t != b)
-            .all(|t| red_allowed(*t, x_range.clone(), y_range.clone()))
-    } else {
-        // Use tiles_y
-        let start = match tiles_y.binary_search_by_key(&(y_range.start, 0), |(x, y)| (*y, *x)) {
-            Ok(e) => e,
-            Err(e) => e,
-        };
-        tiles_y
-            .iter()
-            .skip(start)
-            .take_while(|t| t.1 < y_range.end)
-            .filter(|&&t| t != a && t != b)
-            .all(|t| red_allowed(*t, x_range.clone(), y_range.clone()))
-    };
-    if !corners_ok {
-        return false;
+impl Edge {
+    fn new(a: Tile, b: Tile) -> Result<Self, String> {
+        if a == b {
+            Err(format!("Repeated corner {a:?}"))
+        } else if a.0 == b.0 {
+            Ok(Edge::Vertical {
+                x: a.0,
+                from: a.1.min(b.1),
+                to: a.1.max(b.1),
+            })
+        } else if a.1 == b.1 {
+            Ok(Edge::Horizontal {
+                y: a.1,
+                from: a.0.min(b.0),
+                to: a.0.max(b.0),
+            })
+        } else {
+            Err(format!("Edge from {a:?} to {b:?} is not axis-aligned"))
+        }
     }
 
-    // Check that no line intersects the inside of the rectangle
-    let start = match vert_lines.binary_search_by_key(&x_range.start, |(x, _)| *x) {
-        Ok(e) => e,
-        Err(e) => e,
-    };
-    for (x, line) in vert_lines
-        .iter()
-        .skip(start)
-        .take_while(|(x, _)| *x < x_range.end)
-    {
-        if x_range.start < *x
-            && x_range.end > *x + 1
-            && (y_range.start + 1).max(*line.start()) < (y_range.end - 1).min(line.end() + 1)
-        {
-            return false;
+    /// Bounding box as ((min x, min y), (max x, max y))
+    fn bounds(&self) -> (Tile, Tile) {
+        match *self {
+            Edge::Vertical { x, from, to } => ((x, from), (x, to)),
+            Edge::Horizontal { y, from, to } => ((from, y), (to, y)),
         }
     }
-    let start = match hori_lines.binary_search_by_key(&y_range.start, |(y, _)| *y) {
-        Ok(e) => e,
-        Err(e) => e,
-    };
-    for (y, line) in hori_lines
-        .iter()
-        .skip(start)
-        .take_while(|(y, _)| *y < y_range.end)
-    {
-        if y_range.start < *y
-            && y_range.end > *y + 1
-            && (x_range.start + 1).max(*line.start()) < (x_range.end - 1).min(line.end() + 1)
-        {
-            return false;
-        }
+
+    fn touches(&self, other: &Edge) -> bool {
+        let (a0, a1) = self.bounds();
+        let (b0, b1) = other.bounds();
+        a0.0 <= b1.0 && b0.0 <= a1.0 && a0.1 <= b1.1 && b0.1 <= a1.1
     }
-    true
 }
 
-fn part2(input: String) {
-    let tiles = parse_input(&input);
+/// Orthogonal polygon through the centers of its corner tiles. The plane is
+/// split into cells at every corner coordinate and the coordinate after it,
+/// so that all tiles in a cell are either inside or outside.
+struct Polygon {
+    corners: Vec<Tile>,
+    xs: Vec<u32>,
+    ys: Vec<u32>,
+    /// `outside[j][i]` counts the cells outside of the polygon with
+    /// column < i and row < j.
+    outside: Vec<Vec<u32>>,
+}
 
-    let mut vert_lines = Vec::new();
-    let mut hori_lines = Vec::new();
-    let mut prev = *tiles.last().unwrap();
-    for &t in &tiles {
-        if t.0 == prev.0 {
-            vert_lines.push((t.0, t.1.min(prev.1)..=t.1.max(prev.1)));
-        } else {
-            debug_assert_eq!(t.1, prev.1);
-            hori_lines.push((t.1, t.0.min(prev.0)..=t.0.max(prev.0)));
+impl Polygon {
+    /// Build the polygon from a closed loop of corners, checking that all
+    /// edges are axis-aligned and that no two edges cross or touch, except
+    /// for neighbors sharing a corner.
+    fn new(corners: Vec<Tile>) -> Result<Self, String> {
+        let n = corners.len();
+        if n < 4 {
+            return Err(format!("A loop needs at least 4 corners, got {n}"));
+        }
+        let edges = (0..n)
+            .map(|i| Edge::new(corners[i], corners[(i + 1) % n]))
+            .collect::<Result<Vec<_>, _>>()?;
+        for i in 0..n {
+            for j in i + 1..n {
+                let adjacent = j == i + 1 || (i == 0 && j == n - 1);
+                if adjacent {
+                    // Collinear neighbors may continue straight on, but not
+                    // both lie on the same side of their common corner
+                    if let (Edge::Vertical { .. }, Edge::Vertical { .. })
+                    | (Edge::Horizontal { .. }, Edge::Horizontal { .. }) = (edges[i], edges[j])
+                    {
+                        let c = if j == i + 1 { corners[j] } else { corners[0] };
+                        let (e0, e1) = (edges[i].bounds(), edges[j].bounds());
+                        if (e0.0 < c && e1.0 < c) || (e0.1 > c && e1.1 > c) {
+                            return Err(format!("Edges fold back onto each other at {c:?}"));
+                        }
+                    }
+                } else if edges[i].touches(&edges[j]) {
+                    return Err(format!(
+                        "Edges {:?} and {:?} intersect",
+                        edges[i].bounds(),
+                        edges[j].bounds()
+                    ));
+                }
+            }
+        }
+
+        let compress = |coords: Vec<u32>| {
+            let mut c: Vec<u32> = coords.into_iter().flat_map(|c| [c, c + 1]).collect();
+            c.sort_unstable();
+            c.dedup();
+            c
+        };
+        let xs = compress(corners.iter().map(|c| c.0).collect());
+        let ys = compress(corners.iter().map(|c| c.1).collect());
+        let (w, h) = (xs.len() - 1, ys.len() - 1);
+
+        // Tiles on an edge belong to the polygon
+        let mut inside = vec![vec![false; w]; h];
+        for e in &edges {
+            let ((x0, y0), (x1, y1)) = e.bounds();
+            let (i0, i1) = (
+                xs.binary_search(&x0).unwrap(),
+                xs.binary_search(&x1).unwrap(),
+            );
+            let (j0, j1) = (
+                ys.binary_search(&y0).unwrap(),
+                ys.binary_search(&y1).unwrap(),
+            );
+            for row in &mut inside[j0..=j1] {
+                row[i0..=i1].fill(true);
+            }
+        }
+        // For the others, count crossings of vertical edges when going right
+        for (j, row) in inside.iter_mut().enumerate() {
+            let y = ys[j];
+            let mut crossings: Vec<u32> = edges
+                .iter()
+                .filter_map(|e| match *e {
+                    Edge::Vertical { x, from, to } if from <= y && y < to => Some(x),
+                    _ => None,
+                })
+                .collect();
+            crossings.sort_unstable();
+            let mut crossed = 0;
+            for (i, cell) in row.iter_mut().enumerate() {
+                while crossed < crossings.len() && crossings[crossed] < xs[i] {
+                    crossed += 1;
+                }
+                *cell |= crossed % 2 == 1;
+            }
+        }
+
+        let mut outside = vec![vec![0; w + 1]; h + 1];
+        for j in 0..h {
+            for i in 0..w {
+                outside[j + 1][i + 1] = outside[j][i + 1] + outside[j + 1][i] - outside[j][i]
+                    + u32::from(!inside[j][i]);
+            }
         }
-        prev = t;
+        Ok(Polygon {
+            corners,
+            xs,
+            ys,
+            outside,
+        })
     }
-    vert_lines.sort_by_key(|(x, _)| *x);
-    hori_lines.sort_by_key(|(y, _)| *y);
 
-    let mut tiles_x = tiles.clone();
-    tiles_x.sort();
-    let mut tiles_y = tiles.clone();
-    tiles_y.sort_by_key(|(x, y)| (*y, *x));
-    let mut largest = 0;
-    for (idx, t1) in tiles.iter().enumerate() {
-        for t2 in tiles.iter().take(idx) {
-            let a = area(*t1, *t2);
-            if a > largest && is_contained(*t1, *t2, &tiles_x, &tiles_y, &vert_lines, &hori_lines) {
-                largest = a;
+    /// Index of the compressed cell containing `c`
+    fn cell(coords: &[u32], c: u32) -> Option<usize> {
+        let i = coords.partition_point(|&x| x <= c);
+        (i > 0 && i < coords.len()).then(|| i - 1)
+    }
+
+    /// Whether all tiles of the rectangle spanned by the two corner tiles
+    /// are inside of the polygon
+    fn contains(&self, a: Tile, b: Tile) -> bool {
+        let cells = (
+            Self::cell(&self.xs, a.0.min(b.0)),
+            Self::cell(&self.xs, a.0.max(b.0)),
+            Self::cell(&self.ys, a.1.min(b.1)),
+            Self::cell(&self.ys, a.1.max(b.1)),
+        );
+        let (Some(i0), Some(i1), Some(j0), Some(j1)) = cells else {
+            return false;
+        };
+        let o = &self.outside;
+        o[j1 + 1][i1 + 1] + o[j0][i0] == o[j0][i1 + 1] + o[j1 + 1][i0]
+    }
+
+    /// Largest rectangle inside the polygon that has corners at two of the
+    /// polygon's corners
+    fn largest_rectangle(&self) -> Option<(Tile, Tile, u64)> {
+        let mut largest = None;
+        let mut largest_area = 0;
+        for (idx, &t1) in self.corners.iter().enumerate() {
+            for &t2 in self.corners.iter().take(idx) {
+                let a = area(t1, t2);
+                if a > largest_area && self.contains(t1, t2) {
+                    largest_area = a;
+                    largest = Some((t1, t2, a));
+                }
             }
         }
+        largest
+    }
+}
+
+fn part2(input: String) {
+    let polygon = match Polygon::new(parse_input(&input)) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Invalid input: {e}");
+            return;
+        }
+    };
+    let (a, b, largest) = polygon.largest_rectangle().unwrap();
+    if util::verbose() {
+        println!("Corners: {},{} and {},{}", a.0, a.1, b.0, b.1);
     }
     println!("{largest}");
 }
@@ -20,6 +20,8 @@ pub fn get_input(fname: &str) -> io::Result<(String, bool)> {
     for arg in env::args_os().skip(1) {
         if arg == "2" {
             part_2 = true;
+        } else if arg == "-v" {
+            // Queried separately through `verbose`
         } else if arg != "1" {
             input_path = Some(arg);
         }
@@ -32,6 +34,12 @@ pub fn get_input(fname: &str) -> io::Result<(String, bool)> {
     Ok((input, part_2))
 }
 
+/// Whether `-v` was given on the command line, asking for more output than
+/// just the answer.
+pub fn verbose() -> bool {
+    env::args_os().skip(1).any(|arg| arg == "-v")
+}
+
 #[macro_export]
 macro_rules! aoc_main {
     () => {